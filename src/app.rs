@@ -6,7 +6,11 @@ use egui::{Color32, FontData, FontDefinitions, FontFamily, RichText, TextEdit};
 use poll_promise::Promise;
 use rfd::FileDialog;
 
-use crate::excel::{SplitResult, split_excel_file};
+use crate::excel::{
+    BYTES_PER_MB, Collation, ColumnGroups, DatePeriod, Dedup, KeepTogether, OutputColumn,
+    SourceColumn, SplitMode, SplitOptions, SplitResult, VerifyReport, list_columns,
+    parse_row_ranges, parse_sample_parts, parse_sort_keys, split_excel_file, verify_split,
+};
 
 #[cfg(not(target_arch = "wasm32"))]
 use std::fs;
@@ -14,20 +18,94 @@ use std::fs;
 pub struct ExcelHelperApp {
     header_row_input: String,
     row_count_input: String,
+    file_count_input: String,
+    size_limit_input: String,
+    date_column_input: String,
+    date_period: DatePeriod,
+    row_ranges_input: String,
+    section_markers_input: String,
+    section_header_input: String,
+    key_columns_input: String,
+    column_groups_input: String,
+    sample_parts_input: String,
+    sample_seed_input: String,
+    sample_stratify_input: String,
+    sample_shuffle: bool,
+    split_by: SplitBy,
+    keep_together_enabled: bool,
+    keep_column_input: String,
+    keep_tolerance_input: String,
+    filter_input: String,
+    drop_hidden: bool,
+    footer_input: String,
+    sheet_name_input: String,
+    dedup_enabled: bool,
+    dedup_keys_input: String,
+    dedup_keep_last: bool,
+    dedup_trim: bool,
+    dedup_ignore_case: bool,
+    dedup_fold_width: bool,
+    sort_input: String,
+    collation: Collation,
+    output_columns_enabled: bool,
+    output_columns: Vec<ColumnChoice>,
     selected_file: Option<PathBuf>,
     status: StatusMessage,
     split_promise: Option<Promise<AnyResult<SplitResult>>>,
+    verify_promise: Option<Promise<AnyResult<VerifyReport>>>,
+    columns_promise: Option<Promise<AnyResult<Vec<SourceColumn>>>>,
     fonts_configured: bool,
 }
 
+/// A source column in the output column list, in the order the user arranged.
+struct ColumnChoice {
+    letter: String,
+    header: String,
+    included: bool,
+    rename: String,
+}
+
 impl Default for ExcelHelperApp {
     fn default() -> Self {
         Self {
             header_row_input: "1".into(),
             row_count_input: "500".into(),
+            file_count_input: "8".into(),
+            size_limit_input: "10".into(),
+            date_column_input: String::new(),
+            date_period: DatePeriod::Month,
+            row_ranges_input: String::new(),
+            section_markers_input: "小计, 合计".into(),
+            section_header_input: "0".into(),
+            key_columns_input: String::new(),
+            column_groups_input: "10".into(),
+            sample_parts_input: "10%".into(),
+            sample_seed_input: String::new(),
+            sample_stratify_input: String::new(),
+            sample_shuffle: false,
+            split_by: SplitBy::RowsPerFile,
+            keep_together_enabled: false,
+            keep_column_input: String::new(),
+            keep_tolerance_input: "0".into(),
+            filter_input: String::new(),
+            drop_hidden: false,
+            footer_input: String::new(),
+            sheet_name_input: String::new(),
+            dedup_enabled: false,
+            dedup_keys_input: String::new(),
+            dedup_keep_last: false,
+            dedup_trim: true,
+            dedup_ignore_case: false,
+            dedup_fold_width: false,
+            sort_input: String::new(),
+            collation: Collation::CodePoint,
+            output_columns_enabled: false,
+            output_columns: Vec::new(),
             selected_file: None,
             status: StatusMessage::Idle,
             split_promise: None,
+            verify_promise: None,
+            columns_promise: None,
             fonts_configured: false,
         }
     }
@@ -75,11 +153,61 @@ impl ExcelHelperApp {
             .pick_file()
         {
             self.selected_file = Some(path);
+            self.load_columns();
+        }
+    }
+
+    /// Reads the header of the chosen file in the background to list its columns.
+    fn load_columns(&mut self) {
+        let Some(path) = self.selected_file.clone() else {
+            return;
+        };
+        let header_rows = self.parse_header_rows().unwrap_or(1);
+        self.columns_promise = Some(Promise::spawn_thread("excel-columns", move || {
+            list_columns(&path, header_rows)
+        }));
+    }
+
+    fn build_output_columns(&self) -> Result<Vec<OutputColumn>, String> {
+        if !self.output_columns_enabled {
+            return Ok(Vec::new());
+        }
+        let columns: Vec<OutputColumn> = self
+            .output_columns
+            .iter()
+            .filter(|choice| choice.included)
+            .map(|choice| OutputColumn {
+                source: choice.letter.clone(),
+                header: Some(choice.rename.trim().to_string()).filter(|name| !name.is_empty()),
+            })
+            .collect();
+        if columns.is_empty() {
+            return Err("请至少选择一个输出列".into());
+        }
+        Ok(columns)
+    }
+
+    fn is_busy(&self) -> bool {
+        self.split_promise.is_some() || self.verify_promise.is_some()
+    }
+
+    fn load_row_ranges(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("文本文件", &["txt", "csv"])
+            .set_title("选择包含行范围的文本文件")
+            .pick_file()
+        else {
+            return;
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(text) => self.row_ranges_input = text.trim().to_string(),
+            Err(err) => self.status = StatusMessage::error(format!("无法读取行范围文件: {err}")),
         }
     }
 
     fn start_split(&mut self) {
-        if self.split_promise.is_some() {
+        if self.is_busy() {
             return;
         }
 
@@ -99,26 +227,100 @@ impl ExcelHelperApp {
             }
         };
 
-        let row_limit = match self.parse_row_limit() {
-            Ok(value) => value,
+        // A blank seed gets a fresh one that stays in the form, so the sample can be redrawn.
+        if self.split_by == SplitBy::Sample && self.sample_seed_input.trim().is_empty() {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.subsec_nanos())
+                .unwrap_or_default();
+            self.sample_seed_input = (nanos % 1_000_000).to_string();
+        }
+
+        let mode = match self.build_mode(header_rows) {
+            Ok(mode) => mode,
             Err(msg) => {
                 self.status = StatusMessage::error(msg);
                 return;
             }
         };
 
-        if row_limit <= header_rows {
-            self.status = StatusMessage::error("拆分行数必须大于表头行数");
-            return;
-        }
+        let keep_together = match self.build_keep_together() {
+            Ok(keep) => keep,
+            Err(msg) => {
+                self.status = StatusMessage::error(msg);
+                return;
+            }
+        };
 
-        let promise = Promise::spawn_thread("excel-split", move || {
-            split_excel_file(&path, row_limit, header_rows)
-        });
+        let sort = match parse_sort_keys(&self.sort_input) {
+            Ok(sort) => sort,
+            Err(err) => {
+                self.status = StatusMessage::error(err.to_string());
+                return;
+            }
+        };
+
+        let output_columns = match self.build_output_columns() {
+            Ok(columns) => columns,
+            Err(msg) => {
+                self.status = StatusMessage::error(msg);
+                return;
+            }
+        };
+
+        let options = SplitOptions {
+            header_rows,
+            mode,
+            keep_together,
+            filter: Some(self.filter_input.trim().to_string()).filter(|text| !text.is_empty()),
+            dedup: self.dedup_enabled.then(|| Dedup {
+                key_columns: self.dedup_keys_input.trim().to_string(),
+                keep_last: self.dedup_keep_last,
+                trim: self.dedup_trim,
+                ignore_case: self.dedup_ignore_case,
+                fold_width: self.dedup_fold_width,
+            }),
+            sort,
+            collation: self.collation,
+            output_columns,
+            drop_hidden: self.drop_hidden,
+            footer: Some(self.footer_input.trim().to_string()).filter(|text| !text.is_empty()),
+            sheet_name: Some(self.sheet_name_input.trim().to_string())
+                .filter(|text| !text.is_empty()),
+        };
+        let promise =
+            Promise::spawn_thread("excel-split", move || split_excel_file(&path, &options));
         self.split_promise = Some(promise);
         self.status = StatusMessage::info("正在拆分，请稍候...");
     }
 
+    fn start_verify(&mut self) {
+        if self.is_busy() {
+            return;
+        }
+
+        let path = match self.selected_file.clone() {
+            Some(path) => path,
+            None => {
+                self.status = StatusMessage::error("请先选择 Excel 文件");
+                return;
+            }
+        };
+
+        let header_rows = match self.parse_header_rows() {
+            Ok(value) => value,
+            Err(msg) => {
+                self.status = StatusMessage::error(msg);
+                return;
+            }
+        };
+
+        let promise =
+            Promise::spawn_thread("excel-verify", move || verify_split(&path, header_rows));
+        self.verify_promise = Some(promise);
+        self.status = StatusMessage::info("正在校验拆分结果，请稍候...");
+    }
+
     fn parse_header_rows(&self) -> Result<usize, String> {
         let trimmed = self.header_row_input.trim();
         if trimmed.is_empty() {
@@ -135,6 +337,159 @@ impl ExcelHelperApp {
         Ok(value)
     }
 
+    fn build_mode(&self, header_rows: usize) -> Result<SplitMode, String> {
+        match self.split_by {
+            SplitBy::RowsPerFile => {
+                let chunk_size = self.parse_row_limit()?;
+                if chunk_size <= header_rows {
+                    return Err("拆分行数必须大于表头行数".into());
+                }
+                Ok(SplitMode::RowsPerFile { chunk_size })
+            }
+            SplitBy::FileCount => Ok(SplitMode::FileCount {
+                files: self.parse_file_count()?,
+            }),
+            SplitBy::FileSize => {
+                let megabytes = self.parse_size_limit()?;
+                Ok(SplitMode::FileSize {
+                    max_bytes: (megabytes * BYTES_PER_MB) as u64,
+                })
+            }
+            SplitBy::DatePeriod => {
+                let column = self.date_column_input.trim();
+                if column.is_empty() {
+                    return Err("请输入日期列（表头名称或列字母）".into());
+                }
+                Ok(SplitMode::DatePeriod {
+                    column: column.to_string(),
+                    period: self.date_period,
+                })
+            }
+            SplitBy::RowRanges => {
+                let ranges =
+                    parse_row_ranges(&self.row_ranges_input).map_err(|err| err.to_string())?;
+                Ok(SplitMode::RowRanges { ranges })
+            }
+            SplitBy::Sections => {
+                let markers = self
+                    .section_markers_input
+                    .split([',', '，', ';', '；', '、'])
+                    .map(str::trim)
+                    .filter(|marker| !marker.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                let trimmed = self.section_header_input.trim();
+                let section_header_rows = if trimmed.is_empty() {
+                    0
+                } else {
+                    trimmed
+                        .parse()
+                        .map_err(|_| "分段表头行数必须是非负整数".to_string())?
+                };
+                Ok(SplitMode::Sections {
+                    markers,
+                    section_header_rows,
+                })
+            }
+            SplitBy::Columns => {
+                let trimmed = self.column_groups_input.trim();
+                if trimmed.is_empty() {
+                    return Err("请输入每个文件的列数或列分组".into());
+                }
+                // A plain number means "columns per file"; anything else lists the groups.
+                let groups = match trimmed.parse::<usize>() {
+                    Ok(0) => return Err("每个文件的列数必须大于 0".into()),
+                    Ok(columns) => ColumnGroups::PerFile { columns },
+                    Err(_) => ColumnGroups::Explicit {
+                        groups: trimmed
+                            .split([';', '；', '\n'])
+                            .map(str::trim)
+                            .filter(|group| !group.is_empty())
+                            .map(str::to_string)
+                            .collect(),
+                    },
+                };
+                Ok(SplitMode::Columns {
+                    key_columns: self.key_columns_input.trim().to_string(),
+                    groups,
+                })
+            }
+            SplitBy::Sample => {
+                let parts =
+                    parse_sample_parts(&self.sample_parts_input).map_err(|err| err.to_string())?;
+                let seed = self
+                    .sample_seed_input
+                    .trim()
+                    .parse()
+                    .map_err(|_| "随机种子必须是非负整数".to_string())?;
+                let stratify = self.sample_stratify_input.trim();
+                Ok(SplitMode::Sample {
+                    parts,
+                    seed,
+                    stratify: (!stratify.is_empty()).then(|| stratify.to_string()),
+                    shuffle: self.sample_shuffle,
+                })
+            }
+        }
+    }
+
+    fn build_keep_together(&self) -> Result<Option<KeepTogether>, String> {
+        if !self.keep_together_enabled || self.split_by != SplitBy::RowsPerFile {
+            return Ok(None);
+        }
+
+        let column = self.keep_column_input.trim();
+        if column.is_empty() {
+            return Err("请输入需要保持完整的分组列（表头名称或列字母）".into());
+        }
+
+        let trimmed = self.keep_tolerance_input.trim();
+        let tolerance = if trimmed.is_empty() {
+            0
+        } else {
+            trimmed
+                .parse()
+                .map_err(|_| "允许超出的行数必须是非负整数".to_string())?
+        };
+
+        Ok(Some(KeepTogether {
+            column: column.to_string(),
+            tolerance,
+        }))
+    }
+
+    fn parse_size_limit(&self) -> Result<f64, String> {
+        let trimmed = self.size_limit_input.trim();
+        if trimmed.is_empty() {
+            return Err("请输入文件大小上限".into());
+        }
+
+        let value: f64 = trimmed
+            .parse()
+            .map_err(|_| "文件大小上限必须是数字（单位 MB）".to_string())?;
+        if !value.is_finite() || value <= 0.0 {
+            return Err("文件大小上限必须大于 0".into());
+        }
+
+        Ok(value)
+    }
+
+    fn parse_file_count(&self) -> Result<usize, String> {
+        let trimmed = self.file_count_input.trim();
+        if trimmed.is_empty() {
+            return Err("请输入文件个数".into());
+        }
+
+        let value: usize = trimmed
+            .parse()
+            .map_err(|_| "文件个数必须是正整数".to_string())?;
+        if value == 0 {
+            return Err("文件个数必须大于 0".into());
+        }
+
+        Ok(value)
+    }
+
     fn parse_row_limit(&self) -> Result<usize, String> {
         let trimmed = self.row_count_input.trim();
         if trimmed.is_empty() {
@@ -163,6 +518,38 @@ impl ExcelHelperApp {
                 }
             }
         }
+
+        if let Some(promise) = self.columns_promise.take() {
+            match promise.try_take() {
+                Ok(Ok(columns)) => {
+                    self.output_columns = columns
+                        .into_iter()
+                        .map(|column| ColumnChoice {
+                            letter: column.letter,
+                            header: column.header,
+                            included: true,
+                            rename: String::new(),
+                        })
+                        .collect();
+                }
+                Ok(Err(err)) => self.status = StatusMessage::error(format!("读取列失败: {err}")),
+                Err(promise) => {
+                    self.columns_promise = Some(promise);
+                }
+            }
+        }
+
+        if let Some(promise) = self.verify_promise.take() {
+            match promise.try_take() {
+                Ok(result) => match result {
+                    Ok(report) => self.handle_verify(report),
+                    Err(err) => self.status = StatusMessage::error(format!("校验失败: {err}")),
+                },
+                Err(promise) => {
+                    self.verify_promise = Some(promise);
+                }
+            }
+        }
     }
 
     fn handle_success(&mut self, summary: SplitResult) {
@@ -172,19 +559,141 @@ impl ExcelHelperApp {
             summary.header_rows,
             summary.chunks.len()
         );
+        if summary.filtered_rows > 0 {
+            message.push_str(&format!(
+                "\n已按筛选条件排除 {} 行数据。",
+                summary.filtered_rows
+            ));
+        }
+        if summary.hidden_rows > 0 {
+            message.push_str(&format!("\n已删除 {} 行隐藏数据。", summary.hidden_rows));
+        }
+        if let Some(path) = &summary.duplicates_path {
+            message.push_str(&format!(
+                "\n已去除 {} 行重复数据，明细见: {}",
+                summary.duplicate_rows,
+                path.display()
+            ));
+        }
 
         for (idx, chunk) in summary.chunks.iter().enumerate() {
+            let name = chunk
+                .group_key
+                .clone()
+                .unwrap_or_else(|| format!("第{}部分", idx + 1));
             message.push_str(&format!(
-                "\n第{}部分: {} 行（数据 {} 行） -> {}",
-                idx + 1,
+                "\n{}: {} 行（数据 {} 行） -> {}",
+                name,
                 chunk.total_rows,
                 chunk.data_rows,
                 chunk.file_path.display()
             ));
         }
+        message.push_str(&format!("\n清单文件: {}", summary.manifest_path.display()));
+        for warning in &summary.warnings {
+            message.push_str(&format!("\n注意: {warning}"));
+        }
 
         self.status = StatusMessage::success(message);
     }
+
+    fn handle_verify(&mut self, report: VerifyReport) {
+        let mut message = format!(
+            "已校验 {} 个文件：源数据 {} 行，",
+            report.files_checked.len(),
+            report.source_data_rows
+        );
+        if report.expected_data_rows != report.source_data_rows {
+            message.push_str(&format!(
+                "按清单中的拆分选项应输出 {} 行，",
+                report.expected_data_rows
+            ));
+        }
+        message.push_str(&format!("拆分结果数据 {} 行。", report.output_data_rows));
+
+        if report.source_hash_matches == Some(false) {
+            message.push_str("\n源文件在拆分后已被修改（与清单中的校验值不一致）。");
+        }
+        for path in &report.hash_mismatches {
+            message.push_str(&format!("\n文件校验值与清单不一致: {}", path.display()));
+        }
+
+        if report.is_identical() {
+            self.status = StatusMessage::success(format!("{message}\n拆分结果与源文件完全一致。"));
+            return;
+        }
+
+        if report.total_differences > 0 {
+            message.push_str(&format!(
+                "\n共发现 {} 处差异，前 {} 处：",
+                report.total_differences,
+                report.differences.len()
+            ));
+        }
+        for diff in &report.differences {
+            let source = diff.source_cell.as_deref().unwrap_or("（无对应行）");
+            let output = match (&diff.output_file, &diff.output_cell) {
+                (Some(file), Some(cell)) => {
+                    let name = file
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    format!("{name}!{cell}")
+                }
+                _ => "（缺失）".into(),
+            };
+            message.push_str(&format!(
+                "\n源 {source} = \"{}\"，拆分结果 {output} = \"{}\"",
+                diff.expected, diff.actual
+            ));
+        }
+
+        self.status = StatusMessage::error(message);
+    }
+
+    /// The column list: tick to include, type a new header text, move up or down to reorder.
+    fn show_output_columns(&mut self, ui: &mut egui::Ui) {
+        if self.output_columns.is_empty() {
+            ui.label("选择文件后会在这里列出表头中的各列。");
+            return;
+        }
+
+        let mut move_up = None;
+        let mut move_down = None;
+        let last = self.output_columns.len() - 1;
+        egui::ScrollArea::vertical()
+            .max_height(180.0)
+            .show(ui, |ui| {
+                for (idx, choice) in self.output_columns.iter_mut().enumerate() {
+                    ui.push_id(idx, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(
+                                &mut choice.included,
+                                format!("{} {}", choice.letter, choice.header),
+                            );
+                            ui.label("输出表头：");
+                            let edit = TextEdit::singleline(&mut choice.rename)
+                                .hint_text(choice.header.as_str())
+                                .desired_width(120.0);
+                            ui.add(edit);
+                            if ui.add_enabled(idx > 0, egui::Button::new("↑")).clicked() {
+                                move_up = Some(idx);
+                            }
+                            if ui.add_enabled(idx < last, egui::Button::new("↓")).clicked() {
+                                move_down = Some(idx);
+                            }
+                        });
+                    });
+                }
+            });
+
+        if let Some(idx) = move_up {
+            self.output_columns.swap(idx - 1, idx);
+        }
+        if let Some(idx) = move_down {
+            self.output_columns.swap(idx, idx + 1);
+        }
+    }
 }
 
 impl App for ExcelHelperApp {
@@ -194,7 +703,7 @@ impl App for ExcelHelperApp {
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Excel 拆分助手");
-            ui.label("输入表头行数和单个文件的最大行数，程序会把表格按需拆成多个文件并保留表头。");
+            ui.label("输入表头行数，以及单个文件的最大行数或需要的文件个数，程序会把表格按需拆成多个文件并保留表头。");
             ui.separator();
 
             ui.horizontal(|ui| {
@@ -206,11 +715,178 @@ impl App for ExcelHelperApp {
             });
 
             ui.horizontal(|ui| {
-                ui.label("拆分行数：");
-                let edit = TextEdit::singleline(&mut self.row_count_input)
-                    .hint_text("例如 500")
-                    .desired_width(120.0);
+                ui.label("拆分方式：");
+                ui.radio_value(&mut self.split_by, SplitBy::RowsPerFile, "每个文件行数");
+                ui.radio_value(&mut self.split_by, SplitBy::FileCount, "文件个数");
+                ui.radio_value(&mut self.split_by, SplitBy::FileSize, "文件大小");
+                ui.radio_value(&mut self.split_by, SplitBy::DatePeriod, "日期周期");
+                ui.radio_value(&mut self.split_by, SplitBy::RowRanges, "行范围");
+                ui.radio_value(&mut self.split_by, SplitBy::Sections, "分段");
+                ui.radio_value(&mut self.split_by, SplitBy::Columns, "按列");
+                ui.radio_value(&mut self.split_by, SplitBy::Sample, "随机抽样");
+            });
+
+            ui.horizontal(|ui| match self.split_by {
+                SplitBy::RowsPerFile => {
+                    ui.label("拆分行数：");
+                    let edit = TextEdit::singleline(&mut self.row_count_input)
+                        .hint_text("例如 500")
+                        .desired_width(120.0);
+                    ui.add(edit);
+                }
+                SplitBy::FileCount => {
+                    ui.label("文件个数：");
+                    let edit = TextEdit::singleline(&mut self.file_count_input)
+                        .hint_text("例如 8")
+                        .desired_width(120.0);
+                    ui.add(edit);
+                }
+                SplitBy::FileSize => {
+                    ui.label("大小上限 (MB)：");
+                    let edit = TextEdit::singleline(&mut self.size_limit_input)
+                        .hint_text("例如 10")
+                        .desired_width(120.0);
+                    ui.add(edit);
+                    ui.label("（按 xlsx 文件大小计算，不支持 CSV 输出）");
+                }
+                SplitBy::DatePeriod => {
+                    ui.label("日期列：");
+                    let edit = TextEdit::singleline(&mut self.date_column_input)
+                        .hint_text("例如 日期 或 C")
+                        .desired_width(100.0);
+                    ui.add(edit);
+                    egui::ComboBox::from_id_source("date_period")
+                        .selected_text(self.date_period.label())
+                        .show_ui(ui, |ui| {
+                            for period in DatePeriod::ALL {
+                                ui.selectable_value(&mut self.date_period, period, period.label());
+                            }
+                        });
+                }
+                SplitBy::RowRanges => {
+                    ui.label("行范围：");
+                    let edit = TextEdit::singleline(&mut self.row_ranges_input)
+                        .hint_text("例如 2-120, 121-400, 401- 或拆分点 121, 401")
+                        .desired_width(260.0);
+                    ui.add(edit);
+                    if ui.button("从文件读取").clicked() {
+                        self.load_row_ranges();
+                    }
+                }
+                SplitBy::Sections => {
+                    ui.label("分段标记：");
+                    let edit = TextEdit::singleline(&mut self.section_markers_input)
+                        .hint_text("空行之外的分隔行，例如 小计, 合计")
+                        .desired_width(140.0);
+                    ui.add(edit);
+                    ui.label("分段自带表头行数：");
+                    let edit = TextEdit::singleline(&mut self.section_header_input)
+                        .hint_text("0 表示沿用表头")
+                        .desired_width(40.0);
+                    ui.add(edit);
+                }
+                SplitBy::Columns => {
+                    ui.label("关键列：");
+                    let edit = TextEdit::singleline(&mut self.key_columns_input)
+                        .hint_text("每个文件都保留，例如 A-B 或 编号, 姓名")
+                        .desired_width(140.0);
+                    ui.add(edit);
+                    ui.label("列分组：");
+                    let edit = TextEdit::singleline(&mut self.column_groups_input)
+                        .hint_text("每个文件列数，或 C-M; N-Z")
+                        .desired_width(140.0);
+                    ui.add(edit);
+                }
+                SplitBy::Sample => {
+                    ui.label("抽样：");
+                    let edit = TextEdit::singleline(&mut self.sample_parts_input)
+                        .hint_text("10%、80/20 或 5（行）")
+                        .desired_width(100.0);
+                    ui.add(edit);
+                    ui.label("随机种子：");
+                    let edit = TextEdit::singleline(&mut self.sample_seed_input)
+                        .hint_text("留空自动生成")
+                        .desired_width(80.0);
+                    ui.add(edit);
+                    ui.label("分层列：");
+                    let edit = TextEdit::singleline(&mut self.sample_stratify_input)
+                        .hint_text("可选，例如 部门")
+                        .desired_width(80.0);
+                    ui.add(edit);
+                    ui.checkbox(&mut self.sample_shuffle, "打乱行顺序");
+                }
+            });
+
+            if self.split_by == SplitBy::RowsPerFile {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.keep_together_enabled, "保持分组完整");
+                    ui.add_enabled_ui(self.keep_together_enabled, |ui| {
+                        ui.label("分组列：");
+                        let edit = TextEdit::singleline(&mut self.keep_column_input)
+                            .hint_text("例如 订单号 或 A")
+                            .desired_width(100.0);
+                        ui.add(edit);
+                        ui.label("允许超出行数：");
+                        let edit = TextEdit::singleline(&mut self.keep_tolerance_input)
+                            .hint_text("例如 20")
+                            .desired_width(60.0);
+                        ui.add(edit);
+                    });
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("筛选条件：");
+                let edit = TextEdit::singleline(&mut self.filter_input)
+                    .hint_text("可选，例如 状态 = 已完成 AND 金额 > 1000")
+                    .desired_width(320.0);
+                ui.add(edit);
+                ui.checkbox(&mut self.drop_hidden, "删除隐藏的行和列");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("打印页脚：");
+                let edit = TextEdit::singleline(&mut self.footer_input)
+                    .hint_text("可选，例如 第 {index} 部分 第 &P 页 / 共 &N 页")
+                    .desired_width(320.0);
                 ui.add(edit);
+                ui.label("工作表名称：");
+                let edit = TextEdit::singleline(&mut self.sheet_name_input)
+                    .hint_text("默认沿用源工作表名，例如 {sheet}_{index}")
+                    .desired_width(160.0);
+                ui.add(edit);
+            });
+
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.dedup_enabled, "去除重复行");
+                ui.add_enabled_ui(self.dedup_enabled, |ui| {
+                    ui.label("比较列：");
+                    let edit = TextEdit::singleline(&mut self.dedup_keys_input)
+                        .hint_text("留空比较整行，例如 姓名, 手机号")
+                        .desired_width(160.0);
+                    ui.add(edit);
+                    ui.radio_value(&mut self.dedup_keep_last, false, "保留首次出现");
+                    ui.radio_value(&mut self.dedup_keep_last, true, "保留最后出现");
+                    ui.checkbox(&mut self.dedup_trim, "忽略首尾空格");
+                    ui.checkbox(&mut self.dedup_ignore_case, "忽略大小写");
+                    ui.checkbox(&mut self.dedup_fold_width, "忽略全角/半角");
+                });
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("排序：");
+                let edit = TextEdit::singleline(&mut self.sort_input)
+                    .hint_text("可选，例如 部门, 金额 降序")
+                    .desired_width(320.0);
+                ui.add(edit);
+                ui.label("文字顺序：");
+                egui::ComboBox::from_id_source("collation")
+                    .selected_text(self.collation.label())
+                    .show_ui(ui, |ui| {
+                        for collation in Collation::ALL {
+                            ui.selectable_value(&mut self.collation, collation, collation.label());
+                        }
+                    });
             });
 
             ui.horizontal_wrapped(|ui| {
@@ -227,12 +903,37 @@ impl App for ExcelHelperApp {
                 }
             });
 
-            let busy = self.split_promise.is_some();
-            let button = ui.add_enabled(!busy, egui::Button::new("拆分 (Split)"));
-            if button.clicked() {
-                self.start_split();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.output_columns_enabled, "自定义输出列");
+                let reload = ui.add_enabled(
+                    self.selected_file.is_some() && self.columns_promise.is_none(),
+                    egui::Button::new("重新读取列"),
+                );
+                if reload.clicked() {
+                    self.load_columns();
+                }
+                if self.columns_promise.is_some() {
+                    ui.spinner();
+                }
+            });
+
+            if self.output_columns_enabled {
+                self.show_output_columns(ui);
             }
 
+            let busy = self.is_busy();
+            ui.horizontal(|ui| {
+                let button = ui.add_enabled(!busy, egui::Button::new("拆分 (Split)"));
+                if button.clicked() {
+                    self.start_split();
+                }
+
+                let verify = ui.add_enabled(!busy, egui::Button::new("校验 (Verify)"));
+                if verify.clicked() {
+                    self.start_verify();
+                }
+            });
+
             if busy {
                 ui.add_space(4.0);
                 ui.horizontal(|ui| {
@@ -249,6 +950,19 @@ impl App for ExcelHelperApp {
     }
 }
 
+/// Which split parameter the user is editing in the form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SplitBy {
+    RowsPerFile,
+    FileCount,
+    FileSize,
+    DatePeriod,
+    RowRanges,
+    Sections,
+    Columns,
+    Sample,
+}
+
 #[derive(Debug, Clone)]
 enum StatusMessage {
     Idle,
@@ -1,18 +1,164 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result, anyhow};
 use calamine::{Data, Reader, open_workbook_auto};
 use quick_xml::{Reader as XmlReader, events::Event};
-use rust_xlsxwriter::{Format, Workbook};
+use rust_xlsxwriter::{DocProperties, Format, Note, Url, Workbook, Worksheet, XlsxError};
+use serde::{Deserialize, Serialize};
 use zip::ZipArchive;
 
+use comments::CellNote;
+use conditional::{ChunkConditionalRule, ConditionalRule};
+use filter::Filter;
+use formats::NumberFormats;
+use hyperlinks::Hyperlink;
+use images::SheetImage;
+use outline::{ChunkOutline, SheetOutline};
+use print::PrintSetup;
+use validations::{ChunkValidation, Validation};
+
+mod collation;
+mod columns;
+mod comments;
+mod conditional;
+mod dedup;
+mod filter;
+mod formats;
+mod hyperlinks;
+mod identifiers;
+mod images;
+mod manifest;
+mod outline;
+mod period;
+mod print;
+mod properties;
+mod ranges;
+mod sample;
+mod sections;
+mod sort;
+mod validations;
+mod verify;
+
+pub use collation::Collation;
+pub use columns::{ColumnGroups, OutputColumn, SourceColumn, list_columns};
+pub use dedup::Dedup;
+pub use period::DatePeriod;
+pub use ranges::{RowRange, parse_row_ranges};
+pub use sample::{SampleSize, parse_sample_parts};
+pub use sort::{SortKey, parse_sort_keys};
+pub use verify::{VerifyReport, verify_split};
+
+/// Parameters of a split job, also recorded verbatim in the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitOptions {
+    pub header_rows: usize,
+    pub mode: SplitMode,
+    /// Only used with [`SplitMode::RowsPerFile`].
+    #[serde(default)]
+    pub keep_together: Option<KeepTogether>,
+    /// Only data rows matching this expression are split, e.g. `状态 = 已完成 AND 金额 > 1000`.
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Repeated data rows are removed (after filtering) and listed in a separate report file.
+    #[serde(default)]
+    pub dedup: Option<Dedup>,
+    /// Data rows are sorted by these keys (after filtering) before they are split.
+    #[serde(default)]
+    pub sort: Vec<SortKey>,
+    /// Order of text values wherever rows or groups are ordered by text, e.g. pinyin for names.
+    #[serde(default)]
+    pub collation: Collation,
+    /// Columns written to the outputs, in this order and with optional new header texts.
+    /// Empty keeps every column as it is.
+    #[serde(default)]
+    pub output_columns: Vec<OutputColumn>,
+    /// Hidden data rows and hidden columns are left out of the outputs instead of being
+    /// written hidden. Hidden header rows stay hidden.
+    #[serde(default)]
+    pub drop_hidden: bool,
+    /// Footer printed on every page of the outputs instead of the source's, in Excel's
+    /// header and footer codes such as `第 &P 页 / 共 &N 页`; `{index}` stands for the number
+    /// of the output file.
+    #[serde(default)]
+    pub footer: Option<String>,
+    /// Name of the sheet in the outputs, where `{sheet}` stands for the source sheet name,
+    /// `{index}` for the number of the output file and `{key}` for its group key (such as the
+    /// month of a date split). Empty keeps the source sheet name.
+    #[serde(default)]
+    pub sheet_name: Option<String>,
+}
+
+/// Avoids cutting a run of equal values in `column` (e.g. the lines of one order) across two files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeepTogether {
+    /// Header text or column letter.
+    pub column: String,
+    /// How many rows a file may grow beyond its size when that reaches a group end sooner than
+    /// shrinking it would.
+    pub tolerance: usize,
+}
+
+/// How the data rows are distributed over the output files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SplitMode {
+    /// Each file holds at most `chunk_size` rows, header included.
+    RowsPerFile { chunk_size: usize },
+    /// Exactly `files` outputs whose data row counts differ by at most one.
+    FileCount { files: usize },
+    /// Rows are added to a file until its estimated size reaches `max_bytes`; files that still
+    /// end up larger after writing are split again.
+    FileSize { max_bytes: u64 },
+    /// One file per day/week/month/quarter/year of the dates in `column` (header text or letter).
+    DatePeriod { column: String, period: DatePeriod },
+    /// One file per listed range of sheet rows, in the given order.
+    RowRanges { ranges: Vec<RowRange> },
+    /// One file per block of rows separated by blank rows or rows starting with one of
+    /// `markers`. With `section_header_rows > 0` the sheet header is not repeated; instead the
+    /// first rows of every section are that section's own header.
+    Sections {
+        markers: Vec<String>,
+        section_header_rows: usize,
+    },
+    /// Vertical split: every file keeps all rows but only `key_columns` (repeated in each file)
+    /// plus one group of the remaining columns. Columns are header texts, letters or ranges
+    /// such as `A-M`, separated by commas.
+    Columns {
+        key_columns: String,
+        groups: ColumnGroups,
+    },
+    /// Random samples drawn without replacement, one file per part (`10%`, `80/20`, `5` rows).
+    /// The same `seed` always draws the same rows. With `stratify` every value of that column
+    /// is sampled separately; with `shuffle` the files list their rows in random order.
+    Sample {
+        parts: Vec<SampleSize>,
+        seed: u64,
+        stratify: Option<String>,
+        shuffle: bool,
+    },
+}
+
+pub const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
 /// Metadata describing the generated files and helpful stats for the UI.
 pub struct SplitResult {
+    pub sheet_name: String,
     pub total_rows: usize,
     pub header_rows: usize,
+    /// Data rows left out because they did not match [`SplitOptions::filter`].
+    pub filtered_rows: usize,
+    /// Hidden data rows left out, see [`SplitOptions::drop_hidden`].
+    pub hidden_rows: usize,
+    /// Data rows removed as duplicates, see [`SplitOptions::dedup`].
+    pub duplicate_rows: usize,
+    /// Report listing the removed duplicates, written when there were any.
+    pub duplicates_path: Option<PathBuf>,
     pub chunks: Vec<SplitChunk>,
+    pub manifest_path: PathBuf,
+    pub warnings: Vec<String>,
 }
 
 /// Metadata for a single output file.
@@ -20,6 +166,10 @@ pub struct SplitChunk {
     pub file_path: PathBuf,
     pub total_rows: usize,
     pub data_rows: usize,
+    /// First and last source sheet row (1-based) of the data in this file.
+    pub source_rows: Option<(usize, usize)>,
+    pub group_key: Option<String>,
+    pub sha256: String,
 }
 
 #[derive(Debug, Clone)]
@@ -36,26 +186,961 @@ struct ChunkMerge {
     start_col: u16,
     end_col: u16,
     value: String,
+    /// Source column of the value the merge shows, which a column selection may move.
+    source_col: usize,
 }
 
 /// Splits the first worksheet of the given Excel file into multiple files while keeping the header.
-pub fn split_excel_file(
-    source: &Path,
-    chunk_size: usize,
-    header_rows: usize,
-) -> Result<SplitResult> {
-    if chunk_size == 0 {
-        return Err(anyhow!("拆分的行数必须大于 0"));
-    }
+/// A JSON manifest describing the outputs is written next to them.
+pub fn split_excel_file(source: &Path, options: &SplitOptions) -> Result<SplitResult> {
+    let header_rows = options.header_rows;
 
     if header_rows == 0 {
         return Err(anyhow!("表头行数必须大于 0"));
     }
 
-    if chunk_size <= header_rows {
-        return Err(anyhow!("拆分行数必须大于表头行数"));
+    match options.mode {
+        SplitMode::RowsPerFile { chunk_size } => {
+            if chunk_size == 0 {
+                return Err(anyhow!("拆分的行数必须大于 0"));
+            }
+            if chunk_size <= header_rows {
+                return Err(anyhow!("拆分行数必须大于表头行数"));
+            }
+        }
+        SplitMode::FileCount { files } => {
+            if files == 0 {
+                return Err(anyhow!("拆分的文件数必须大于 0"));
+            }
+        }
+        SplitMode::FileSize { max_bytes } => {
+            if max_bytes == 0 {
+                return Err(anyhow!("文件大小上限必须大于 0"));
+            }
+        }
+        SplitMode::DatePeriod { ref column, .. } => {
+            if column.trim().is_empty() {
+                return Err(anyhow!("请指定日期列"));
+            }
+        }
+        SplitMode::RowRanges { ref ranges } => {
+            if ranges.is_empty() {
+                return Err(anyhow!("请至少填写一个行范围"));
+            }
+        }
+        SplitMode::Sections { .. } => {}
+        SplitMode::Columns { ref groups, .. } => match groups {
+            ColumnGroups::PerFile { columns } if *columns == 0 => {
+                return Err(anyhow!("每个文件的列数必须大于 0"));
+            }
+            ColumnGroups::Explicit { groups } if groups.is_empty() => {
+                return Err(anyhow!("请至少填写一个列分组"));
+            }
+            _ => {}
+        },
+        SplitMode::Sample { ref parts, .. } => {
+            if parts.is_empty() {
+                return Err(anyhow!("请填写抽样比例或行数"));
+            }
+        }
+    }
+
+    // Checked again per output file once `{index}` is filled in; the shortest number is counted
+    // here so an overlong footer fails before any file is written.
+    if let Some(footer) = &options.footer
+        && footer
+            .trim()
+            .replace(print::INDEX_PLACEHOLDER, "1")
+            .chars()
+            .count()
+            > print::MAX_HEADER_FOOTER_CHARS
+    {
+        return Err(anyhow!(
+            "页脚文字不能超过 {} 个字符",
+            print::MAX_HEADER_FOOTER_CHARS
+        ));
+    }
+
+    // A section's own header rows sit among the data rows, where filtering, sorting or removing
+    // duplicates would move or drop them.
+    if let SplitMode::Sections {
+        section_header_rows,
+        ..
+    } = options.mode
+        && section_header_rows > 0
+        && (options
+            .filter
+            .as_deref()
+            .is_some_and(|text| !text.trim().is_empty())
+            || !options.sort.is_empty()
+            || options.dedup.is_some())
+    {
+        return Err(anyhow!("每段自带表头时不能同时使用筛选、排序或去除重复行"));
+    }
+
+    if options.keep_together.is_some() && !matches!(options.mode, SplitMode::RowsPerFile { .. }) {
+        return Err(anyhow!("“保持分组完整”仅适用于按每个文件行数拆分"));
+    }
+
+    let selection = select_rows(source, options)?;
+    let sheet_name = selection.sheet_name.clone();
+    let (origin, total_rows, width) = (selection.origin, selection.total_rows, selection.width);
+    let header_rows = selection.header.len();
+    let (filtered_rows, hidden_rows) = (selection.filtered_rows, selection.hidden_rows);
+
+    let mut duplicates_path = None;
+    let duplicate_rows = selection.duplicates.len();
+    if !selection.duplicates.is_empty() {
+        duplicates_path = Some(dedup::write_report(
+            source,
+            &selection.header,
+            origin.0,
+            &selection.data_rows,
+            &selection.duplicates,
+        )?);
+    }
+
+    let merges = extract_merge_ranges(source, &sheet_name, origin)?;
+    let mut warnings = Vec::new();
+    let hyperlinks = hyperlinks::extract_hyperlinks(
+        source,
+        &sheet_name,
+        origin,
+        total_rows,
+        width,
+        &mut warnings,
+    )?;
+    let notes = comments::extract_notes(source, &sheet_name, origin)?;
+    let images = images::extract_images(source, &sheet_name, origin, &mut warnings)?;
+    let validations =
+        validations::extract_validations(source, &sheet_name, origin, total_rows, &mut warnings)?;
+    let conditional_formats = conditional::extract_conditional_formats(
+        source,
+        &sheet_name,
+        origin,
+        total_rows,
+        &mut warnings,
+    )?;
+    let print_setup = print::extract_print_setup(source, &sheet_name, &mut warnings)?
+        .with_footer(options.footer.as_deref());
+    let properties = properties::extract_properties(source)?;
+    identifiers::precision_warnings(
+        &selection.header,
+        &selection.data_cells,
+        origin,
+        &mut warnings,
+    );
+    if !options.sort.is_empty() {
+        let broken = sort::broken_merges(&merges, header_rows, &selection.order);
+        if broken > 0 {
+            warnings.push(format!(
+                "排序后有 {broken} 个跨行合并单元格的行不再相邻，输出文件中不再合并"
+            ));
+        }
+    }
+
+    let extras = SheetExtras {
+        properties,
+        merges,
+        hyperlinks,
+        notes,
+        images,
+        validations,
+        conditional_formats,
+        print_setup,
+    };
+    let context = ChunkContext::new(source, options, selection, extras);
+    let chunks = match options.mode {
+        SplitMode::FileSize { max_bytes } => split_by_size(&context, max_bytes, &mut warnings)?,
+        _ => {
+            let plans = context.plan_parts(options, &mut warnings)?;
+            context.write_parts(plans, &mut warnings)?
+        }
+    };
+
+    let mut result = SplitResult {
+        sheet_name,
+        total_rows,
+        header_rows,
+        filtered_rows,
+        hidden_rows,
+        duplicate_rows,
+        duplicates_path,
+        chunks,
+        manifest_path: PathBuf::new(),
+        warnings,
+    };
+    result.manifest_path = manifest::write_manifest(source, options, &result)?;
+    Ok(result)
+}
+
+/// The source sheet of a split and the rows and columns its options select, in output order.
+/// Splitting and verifying both work from it, so they always agree on what the outputs hold.
+struct SplitSelection {
+    sheet_name: String,
+    /// Sheet row and column of the first cell read.
+    origin: (usize, usize),
+    total_rows: usize,
+    width: usize,
+    formats: NumberFormats,
+    header: Vec<Vec<String>>,
+    /// The header as written, with renamed columns.
+    output_header: Vec<Vec<String>>,
+    /// Source columns every output is limited to, in output order; `None` keeps all.
+    output_columns: Option<Vec<usize>>,
+    data_rows: Vec<Vec<String>>,
+    data_cells: Vec<Vec<Data>>,
+    outline: SheetOutline,
+    /// Indices into `data_rows` of the rows to split, in output order.
+    order: Vec<usize>,
+    hidden_rows: usize,
+    filtered_rows: usize,
+    duplicates: Vec<dedup::Duplicate>,
+}
+
+/// Header rows the outputs repeat: sections with their own header take the whole sheet,
+/// stacked tables included.
+fn effective_header_rows(options: &SplitOptions) -> usize {
+    match options.mode {
+        SplitMode::Sections {
+            section_header_rows,
+            ..
+        } if section_header_rows > 0 => 0,
+        _ => options.header_rows,
+    }
+}
+
+/// Reads the first sheet of `source` and applies the row and column options: hidden rows,
+/// filter, duplicates, sort and output columns. Nothing is written.
+fn select_rows(source: &Path, options: &SplitOptions) -> Result<SplitSelection> {
+    let header_rows = effective_header_rows(options);
+    let SourceSheet {
+        name: sheet_name,
+        mut cells,
+        formats,
+        origin,
+    } = read_first_sheet_cells(source)?;
+    if cells.len() < header_rows {
+        return Err(anyhow!("工作表的行数小于指定的表头行数"));
+    }
+
+    let total_rows = cells.len();
+    let data_cells = cells.split_off(header_rows);
+    let header: Vec<Vec<String>> = cells
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| convert_row(row, idx, &formats))
+        .collect();
+    let data_rows: Vec<Vec<String>> = data_cells
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| convert_row(row, header_rows + idx, &formats))
+        .collect();
+
+    let width = header
+        .iter()
+        .chain(&data_rows)
+        .map(Vec::len)
+        .max()
+        .unwrap_or(0);
+    let outline = outline::extract_outline(source, &sheet_name, origin, total_rows, width)?;
+    let mut order: Vec<usize> = (0..data_rows.len()).collect();
+    if options.drop_hidden {
+        order.retain(|&idx| !outline.row_hidden(header_rows + idx));
+    }
+    let hidden_rows = data_rows.len() - order.len();
+    if let Some(text) = options
+        .filter
+        .as_deref()
+        .filter(|text| !text.trim().is_empty())
+    {
+        let filter = Filter::parse(text, &header, origin.1)?;
+        order.retain(|&idx| filter.matches(&data_cells[idx], &data_rows[idx]));
+    }
+    let filtered_rows = data_rows.len() - hidden_rows - order.len();
+
+    let duplicates = match &options.dedup {
+        Some(dedup) => dedup::remove_duplicates(&mut order, dedup, &header, origin.1, &data_rows)?,
+        None => Vec::new(),
+    };
+
+    if !options.sort.is_empty() {
+        sort::sort_rows(
+            &mut order,
+            &options.sort,
+            options.collation,
+            &header,
+            origin.1,
+            &data_cells,
+        )?;
+    }
+
+    let (output_columns, output_header) = if options.output_columns.is_empty() {
+        (None, header.clone())
+    } else {
+        let (columns, output_header) =
+            columns::resolve_output_columns(&options.output_columns, &header, origin.1)?;
+        (Some(columns), output_header)
+    };
+    let output_columns = if options.drop_hidden && (0..width).any(|col| outline.column_hidden(col))
+    {
+        let visible: Vec<usize> = output_columns
+            .unwrap_or_else(|| (0..width).collect())
+            .into_iter()
+            .filter(|&col| !outline.column_hidden(col))
+            .collect();
+        if visible.is_empty() {
+            return Err(anyhow!("所有输出列都是隐藏列，删除后没有可输出的列"));
+        }
+        Some(visible)
+    } else {
+        output_columns
+    };
+
+    Ok(SplitSelection {
+        sheet_name,
+        origin,
+        total_rows,
+        width,
+        formats,
+        header,
+        output_header,
+        output_columns,
+        data_rows,
+        data_cells,
+        outline,
+        order,
+        hidden_rows,
+        filtered_rows,
+        duplicates,
+    })
+}
+
+/// What the outputs carry over from the source sheet besides its cell values.
+#[derive(Default)]
+struct SheetExtras {
+    /// Document properties of the source workbook.
+    properties: DocProperties,
+    merges: Vec<MergeRange>,
+    hyperlinks: Vec<Hyperlink>,
+    notes: Vec<CellNote>,
+    images: Vec<SheetImage>,
+    validations: Vec<Validation>,
+    conditional_formats: Vec<ConditionalRule>,
+    print_setup: PrintSetup,
+}
+
+/// The parsed sheet shared by every output file of one split.
+struct ChunkContext<'a> {
+    source: &'a Path,
+    /// Name of the source sheet and the option naming the output sheets after it.
+    sheet_name: String,
+    sheet_name_template: Option<&'a str>,
+    /// Sheet row and column of the first cell read.
+    origin: (usize, usize),
+    width: usize,
+    header: Vec<Vec<String>>,
+    /// The header as written, with renamed columns.
+    output_header: Vec<Vec<String>>,
+    /// Source columns every output is limited to, in output order; `None` keeps all.
+    output_columns: Option<Vec<usize>>,
+    data_rows: Vec<Vec<String>>,
+    data_cells: Vec<Vec<Data>>,
+    /// Source columns holding identifiers, written as text whatever their cell type.
+    text_columns: Vec<bool>,
+    /// Number formats of the source cells, by sheet row (header rows included) and column.
+    formats: NumberFormats,
+    outline: SheetOutline,
+    extras: SheetExtras,
+    /// Indices into `data_rows` of the rows to split, in output order. Plans refer to positions
+    /// in this list, so filtered-out rows never reach a planner.
+    order: Vec<usize>,
+}
+
+/// The data rows (positions in [`ChunkContext::order`], in output order) that make up one
+/// output file.
+struct ChunkPlan {
+    rows: Vec<usize>,
+    /// Names the file instead of its index, e.g. the month of a date split.
+    group_key: Option<String>,
+    /// Source columns in output order; `None` keeps every column in place.
+    columns: Option<Vec<usize>>,
+}
+
+impl From<Range<usize>> for ChunkPlan {
+    fn from(range: Range<usize>) -> Self {
+        Self {
+            rows: range.collect(),
+            group_key: None,
+            columns: None,
+        }
+    }
+}
+
+impl<'a> ChunkContext<'a> {
+    fn new(
+        source: &'a Path,
+        options: &'a SplitOptions,
+        selection: SplitSelection,
+        extras: SheetExtras,
+    ) -> Self {
+        let text_columns =
+            identifiers::detect_identifier_columns(&selection.header, &selection.data_cells);
+        Self {
+            source,
+            sheet_name: selection.sheet_name,
+            sheet_name_template: options.sheet_name.as_deref(),
+            origin: selection.origin,
+            width: selection.width,
+            header: selection.header,
+            output_header: selection.output_header,
+            output_columns: selection.output_columns,
+            data_rows: selection.data_rows,
+            data_cells: selection.data_cells,
+            text_columns,
+            formats: selection.formats,
+            outline: selection.outline,
+            extras,
+            order: selection.order,
+        }
+    }
+
+    /// Decides the output files of every mode but [`SplitMode::FileSize`], which is planned
+    /// while writing by [`split_by_size`].
+    fn plan_parts(
+        &self,
+        options: &SplitOptions,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<ChunkPlan>> {
+        let header_rows = self.header.len();
+        let plans = match (&options.mode, &options.keep_together) {
+            (SplitMode::RowsPerFile { chunk_size }, Some(keep)) if !self.order.is_empty() => {
+                let column = resolve_column(&keep.column, &self.header, self.origin.1)?;
+                let keys: Vec<&str> = self
+                    .rows()
+                    .into_iter()
+                    .map(|row| row.get(column).map(|v| v.trim()).unwrap_or(""))
+                    .collect();
+                let ranges = plan_keep_together(
+                    &keys,
+                    chunk_size - header_rows,
+                    keep.tolerance,
+                    &keep.column,
+                    warnings,
+                );
+                ranges.into_iter().map(ChunkPlan::from).collect()
+            }
+            (SplitMode::DatePeriod { column, period }, _) if !self.order.is_empty() => {
+                let column_idx = resolve_column(column, &self.header, self.origin.1)?;
+                plan_date_periods(
+                    &self.cells(),
+                    column_idx,
+                    *period,
+                    column,
+                    options.collation,
+                    warnings,
+                )
+            }
+            (SplitMode::RowRanges { ranges }, _) if !self.order.is_empty() => {
+                let ranges = ranges::plan_row_ranges(
+                    ranges,
+                    header_rows,
+                    self.origin.0,
+                    self.data_rows.len(),
+                    warnings,
+                )?;
+                // The ranges name sheet rows; keep only the rows that passed the filter.
+                ranges
+                    .into_iter()
+                    .map(|range| ChunkPlan {
+                        rows: (0..self.order.len())
+                            .filter(|&pos| range.contains(&self.order[pos]))
+                            .collect(),
+                        group_key: None,
+                        columns: None,
+                    })
+                    .collect()
+            }
+            (
+                SplitMode::Sections {
+                    markers,
+                    section_header_rows,
+                },
+                _,
+            ) => sections::plan_section_parts(
+                &self.data_rows,
+                &self.order,
+                markers,
+                *section_header_rows,
+                header_rows,
+                self.origin.0,
+                warnings,
+            ),
+            (
+                SplitMode::Columns {
+                    key_columns,
+                    groups,
+                },
+                _,
+            ) => columns::plan_column_groups(
+                key_columns,
+                groups,
+                &self.header,
+                self.origin.1,
+                self.width,
+                self.order.len(),
+            )?,
+            (
+                SplitMode::Sample {
+                    parts,
+                    seed,
+                    stratify,
+                    shuffle,
+                },
+                _,
+            ) => {
+                let rows = self.rows();
+                let strata = match stratify.as_deref().map(str::trim) {
+                    Some(column) if !column.is_empty() => {
+                        let column = resolve_column(column, &self.header, self.origin.1)?;
+                        Some(
+                            rows.iter()
+                                .map(|row| row.get(column).map(|v| v.trim()).unwrap_or(""))
+                                .collect::<Vec<_>>(),
+                        )
+                    }
+                    _ => None,
+                };
+                sample::plan_samples(
+                    rows.len(),
+                    strata.as_deref(),
+                    parts,
+                    *seed,
+                    *shuffle,
+                    options.collation,
+                    warnings,
+                )
+            }
+            _ => plan_chunks(self.order.len(), header_rows, &options.mode)?
+                .into_iter()
+                .map(ChunkPlan::from)
+                .collect(),
+        };
+        Ok(plans)
     }
 
+    /// Source columns of the output written for `plan`, in output order; `None` keeps all.
+    /// A vertical split keeps its own column order, limited to the selected output columns.
+    fn plan_columns(&self, plan: &ChunkPlan) -> Option<Vec<usize>> {
+        match (&plan.columns, &self.output_columns) {
+            (Some(planned), Some(selected)) => Some(
+                planned
+                    .iter()
+                    .copied()
+                    .filter(|col| selected.contains(col))
+                    .collect(),
+            ),
+            (planned, selected) => planned.clone().or_else(|| selected.clone()),
+        }
+    }
+
+    fn rows(&self) -> Vec<&[String]> {
+        self.order
+            .iter()
+            .map(|&idx| self.data_rows[idx].as_slice())
+            .collect()
+    }
+
+    fn cells(&self) -> Vec<&[Data]> {
+        self.order
+            .iter()
+            .map(|&idx| self.data_cells[idx].as_slice())
+            .collect()
+    }
+
+    fn write_parts(
+        &self,
+        plans: Vec<ChunkPlan>,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<SplitChunk>> {
+        // The last file has the longest number, so an overlong footer fails before any is written.
+        self.extras.print_setup.for_chunk(plans.len(), 0)?;
+        let mut dropped = Vec::new();
+        let chunks = plans
+            .iter()
+            .enumerate()
+            .map(|(idx, plan)| self.write_part(idx + 1, plan, &mut dropped))
+            .collect::<Result<_>>()?;
+        dropped_rules_warning(&dropped, warnings);
+        Ok(chunks)
+    }
+
+    /// Writes the header plus the planned data rows as output number `index`. Rules left out
+    /// of the file because they refer to cells it does not hold are described in `dropped`.
+    fn write_part(
+        &self,
+        index: usize,
+        plan: &ChunkPlan,
+        dropped: &mut Vec<String>,
+    ) -> Result<SplitChunk> {
+        let header_rows = self.header.len();
+        let rows: Vec<usize> = plan.rows.iter().map(|&pos| self.order[pos]).collect();
+        let chunk_data: Vec<&[String]> = rows
+            .iter()
+            .map(|&idx| self.data_rows[idx].as_slice())
+            .collect();
+        let cell_types = CellTypes {
+            cells: rows
+                .iter()
+                .map(|&idx| self.data_cells[idx].as_slice())
+                .collect(),
+            sheet_rows: rows.iter().map(|&idx| header_rows + idx).collect(),
+            formats: &self.formats,
+            text_columns: &self.text_columns,
+        };
+        let path = match &plan.group_key {
+            Some(key) => build_group_output_path(self.source, key),
+            None => build_output_path(self.source, index),
+        };
+        let columns = self.plan_columns(plan);
+        let first = rows.iter().min();
+        let last = rows.iter().max();
+        let source_rows = first.zip(last).map(|(first, last)| {
+            (
+                sheet_row_number(self.origin.0, header_rows + first),
+                sheet_row_number(self.origin.0, header_rows + last),
+            )
+        });
+        let positions = ChunkPositions::new(header_rows, &rows, columns.as_deref());
+        let mut file_dropped = Vec::new();
+        let extras = ChunkExtras {
+            sheet_name: Some(properties::output_sheet_name(
+                self.sheet_name_template,
+                &self.sheet_name,
+                index,
+                plan.group_key.as_deref(),
+            )),
+            properties: Some(properties::chunk_properties(
+                &self.extras.properties,
+                self.source,
+                index,
+                source_rows,
+            )),
+            merges: map_chunk_merges(
+                &self.extras.merges,
+                header_rows,
+                &rows,
+                columns.as_deref(),
+                &self.output_header,
+                &self.data_rows,
+            ),
+            hyperlinks: hyperlinks::map_chunk_hyperlinks(&self.extras.hyperlinks, &positions),
+            notes: comments::map_chunk_notes(&self.extras.notes, &positions),
+            images: images::map_chunk_images(&self.extras.images, &positions),
+            validations: validations::map_chunk_validations(
+                &self.extras.validations,
+                &positions,
+                &mut file_dropped,
+            ),
+            conditional_formats: conditional::map_chunk_conditional_formats(
+                &self.extras.conditional_formats,
+                &positions,
+                &mut file_dropped,
+            ),
+            outline: outline::map_chunk_outline(&self.outline, &positions),
+            print_setup: self.extras.print_setup.for_chunk(index, header_rows)?,
+        };
+        write_chunk(
+            &path,
+            &self.output_header,
+            &chunk_data,
+            Some(&cell_types),
+            columns.as_deref(),
+            &extras,
+        )?;
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        dropped.extend(
+            file_dropped
+                .into_iter()
+                .map(|rule| format!("{file_name} 中的{rule}")),
+        );
+        Ok(SplitChunk {
+            sha256: manifest::sha256_file(&path)?,
+            file_path: path,
+            total_rows: header_rows + chunk_data.len(),
+            data_rows: chunk_data.len(),
+            source_rows,
+            group_key: plan.group_key.clone(),
+        })
+    }
+}
+
+/// Initial guess of written xlsx bytes per byte of [`estimate_row_bytes`]; refined after every
+/// written file from the real file size.
+const INITIAL_SIZE_RATIO: f64 = 0.3;
+
+/// Files are filled up to this share of the limit to leave room for estimation error.
+const SIZE_TARGET_SHARE: f64 = 0.95;
+
+/// Approximate uncompressed XML bytes spent on one cell besides its text.
+const CELL_XML_OVERHEAD: usize = 24;
+
+fn split_by_size(
+    context: &ChunkContext<'_>,
+    max_bytes: u64,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<SplitChunk>> {
+    let rows = context.rows();
+    let data_len = rows.len();
+    let header_bytes: usize = context
+        .header
+        .iter()
+        .map(|row| estimate_row_bytes(row))
+        .sum();
+    let target = max_bytes as f64 * SIZE_TARGET_SHARE;
+    let mut ratio = INITIAL_SIZE_RATIO;
+    let mut chunks = Vec::new();
+    let mut dropped = Vec::new();
+    let mut start = 0;
+
+    loop {
+        let mut end = start;
+        let mut raw_bytes = header_bytes;
+        while end < data_len {
+            let row_bytes = estimate_row_bytes(rows[end]);
+            if end > start && (raw_bytes + row_bytes) as f64 * ratio > target {
+                break;
+            }
+            raw_bytes += row_bytes;
+            end += 1;
+        }
+
+        // Parts that still come out too large are halved and rewritten under the same index.
+        let mut pending = vec![Range { start, end }];
+        while let Some(range) = pending.pop() {
+            let mut chunk_dropped = Vec::new();
+            let chunk = context.write_part(
+                chunks.len() + 1,
+                &ChunkPlan::from(range.clone()),
+                &mut chunk_dropped,
+            )?;
+            let size = std::fs::metadata(&chunk.file_path)?.len();
+            if size > max_bytes && range.len() > 1 {
+                let mid = range.start + range.len() / 2;
+                pending.push(mid..range.end);
+                pending.push(range.start..mid);
+                continue;
+            }
+            if size > max_bytes {
+                warnings.push(format!(
+                    "{} 只包含 1 行数据，但仍有 {:.2} MB，超过了大小上限",
+                    chunk.file_path.display(),
+                    size as f64 / BYTES_PER_MB
+                ));
+            }
+
+            let written_raw: usize = header_bytes
+                + rows[range]
+                    .iter()
+                    .map(|row| estimate_row_bytes(row))
+                    .sum::<usize>();
+            if written_raw > 0 {
+                ratio = size as f64 / written_raw as f64;
+            }
+            chunks.push(chunk);
+            dropped.extend(chunk_dropped);
+        }
+
+        start = end;
+        if start >= data_len {
+            break;
+        }
+    }
+
+    dropped_rules_warning(&dropped, warnings);
+    Ok(chunks)
+}
+
+/// One warning for the validations and conditional formats left out of some outputs.
+fn dropped_rules_warning(dropped: &[String], warnings: &mut Vec<String>) {
+    if let Some(example) = dropped.first() {
+        warnings.push(format!(
+            "有 {} 处数据验证或条件格式引用了拆分后不在同一文件中的单元格，已在相应输出文件中省略（如 {example}）",
+            dropped.len()
+        ));
+    }
+}
+
+fn estimate_row_bytes(row: &[String]) -> usize {
+    row.iter()
+        .map(|value| value.len() + CELL_XML_OVERHEAD)
+        .sum()
+}
+
+/// Like [`SplitMode::RowsPerFile`], but a boundary that falls inside a run of equal `keys` moves
+/// to the closest change of value: backwards anywhere within the file, or forwards by at most
+/// `tolerance` rows. Groups larger than a whole file are cut and reported in `warnings`.
+fn plan_keep_together(
+    keys: &[&str],
+    capacity: usize,
+    tolerance: usize,
+    column: &str,
+    warnings: &mut Vec<String>,
+) -> Vec<Range<usize>> {
+    let is_boundary = |idx: usize| idx == keys.len() || keys[idx - 1] != keys[idx];
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while start < keys.len() {
+        let mut end = (start + capacity).min(keys.len());
+        if !is_boundary(end) {
+            let back = (start + 1..end).rev().find(|&idx| is_boundary(idx));
+            let forward =
+                (end + 1..=(end + tolerance).min(keys.len())).find(|&idx| is_boundary(idx));
+            end = match (back, forward) {
+                (Some(back), Some(forward)) if forward - end < end - back => forward,
+                (Some(back), _) => back,
+                (None, Some(forward)) => forward,
+                (None, None) => {
+                    // Warn once per group, on the file where it starts.
+                    if start == 0 || is_boundary(start) {
+                        let group_end = (end..keys.len())
+                            .find(|&idx| is_boundary(idx + 1))
+                            .map_or(keys.len(), |idx| idx + 1);
+                        warnings.push(format!(
+                            "“{column}”列值为“{}”的分组共 {} 行，超过单个文件可容纳的 {capacity} 行数据，已被拆到多个文件中",
+                            keys[start],
+                            group_end - start
+                        ));
+                    }
+                    end
+                }
+            };
+        }
+        ranges.push(start..end);
+        start = end;
+    }
+
+    ranges
+}
+
+/// Group key of the rows whose date could not be recognised in a date split.
+const UNKNOWN_PERIOD_KEY: &str = "未识别日期";
+
+/// Buckets the data rows by the period of their date in `column`; buckets come out ordered by
+/// their keys in `collation` order, which is chronological since the keys are digits, followed
+/// by the rows without a recognisable date.
+fn plan_date_periods(
+    cells: &[&[Data]],
+    column: usize,
+    period: DatePeriod,
+    column_name: &str,
+    collation: Collation,
+    warnings: &mut Vec<String>,
+) -> Vec<ChunkPlan> {
+    let mut buckets: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let mut unknown = Vec::new();
+    for (idx, row) in cells.iter().enumerate() {
+        match row.get(column).and_then(period::cell_date) {
+            Some(date) => buckets.entry(period.key(date)).or_default().push(idx),
+            None => unknown.push(idx),
+        }
+    }
+
+    let mut buckets: Vec<(String, Vec<usize>)> = buckets.into_iter().collect();
+    buckets.sort_by(|(a, _), (b, _)| collation.compare(a, b));
+    let mut plans: Vec<ChunkPlan> = buckets
+        .into_iter()
+        .map(|(key, rows)| ChunkPlan {
+            rows,
+            group_key: Some(key),
+            columns: None,
+        })
+        .collect();
+
+    if !unknown.is_empty() {
+        warnings.push(format!(
+            "有 {} 行的“{column_name}”列无法识别为日期，已单独写入“{UNKNOWN_PERIOD_KEY}”文件",
+            unknown.len()
+        ));
+        plans.push(ChunkPlan {
+            rows: unknown,
+            group_key: Some(UNKNOWN_PERIOD_KEY.to_string()),
+            columns: None,
+        });
+    }
+
+    plans
+}
+
+/// Decides which data rows (indices after the header) go into each output file.
+/// A sheet without data rows still yields one header-only file.
+fn plan_chunks(data_len: usize, header_rows: usize, mode: &SplitMode) -> Result<Vec<Range<usize>>> {
+    if data_len == 0 {
+        return Ok(vec![Range { start: 0, end: 0 }]);
+    }
+
+    match *mode {
+        SplitMode::RowsPerFile { chunk_size } => {
+            let data_capacity = chunk_size - header_rows;
+            Ok((0..data_len)
+                .step_by(data_capacity)
+                .map(|start| start..(start + data_capacity).min(data_len))
+                .collect())
+        }
+        SplitMode::FileCount { files } => {
+            if files > data_len {
+                return Err(anyhow!(
+                    "拆分的文件数（{files}）不能大于数据行数（{data_len}）"
+                ));
+            }
+            // The first `data_len % files` outputs take one extra row so sizes differ by at most one.
+            let base = data_len / files;
+            let extra = data_len % files;
+            let mut start = 0;
+            Ok((0..files)
+                .map(|idx| {
+                    let len = base + usize::from(idx < extra);
+                    let range = start..start + len;
+                    start += len;
+                    range
+                })
+                .collect())
+        }
+        SplitMode::FileSize { .. } => {
+            unreachable!("按文件大小拆分需要边写边估算，由 split_by_size 处理")
+        }
+        SplitMode::DatePeriod { .. } => unreachable!("按日期拆分由 plan_date_periods 处理"),
+        SplitMode::RowRanges { .. } => unreachable!("按行范围拆分由 plan_row_ranges 处理"),
+        SplitMode::Sections { .. } => unreachable!("按分段拆分由 plan_sections 处理"),
+        SplitMode::Columns { .. } => unreachable!("按列拆分由 plan_column_groups 处理"),
+        SplitMode::Sample { .. } => unreachable!("随机抽样由 plan_samples 处理"),
+    }
+}
+
+/// The typed cells of a source worksheet.
+struct SourceSheet {
+    name: String,
+    cells: Vec<Vec<Data>>,
+    formats: NumberFormats,
+    /// Sheet row and column of `cells[0][0]`; the sheet XML addresses cells by sheet position.
+    origin: (usize, usize),
+}
+
+impl SourceSheet {
+    /// Every cell converted to the text written to the outputs.
+    fn text_rows(&self) -> Vec<Vec<String>> {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| convert_row(row, idx, &self.formats))
+            .collect()
+    }
+}
+
+/// Reads the typed cell values of the first worksheet with their number formats. Dates of
+/// 1904-based workbooks are moved to the 1900 date system.
+fn read_first_sheet_cells(source: &Path) -> Result<SourceSheet> {
     let mut workbook = open_workbook_auto(source)
         .with_context(|| format!("无法打开 Excel 文件: {}", source.display()))?;
 
@@ -69,101 +1154,597 @@ pub fn split_excel_file(
         .worksheet_range(&sheet_name)
         .with_context(|| format!("无法读取工作表 {sheet_name}"))?;
 
-    let rows: Vec<Vec<String>> = range.rows().map(convert_row).collect();
-    if rows.len() < header_rows {
-        return Err(anyhow!("工作表的行数小于指定的表头行数"));
+    let mut cells: Vec<Vec<Data>> = range.rows().map(<[Data]>::to_vec).collect();
+    formats::rebase_1904(&mut cells);
+    let origin = range
+        .start()
+        .map_or((0, 0), |(row, col)| (row as usize, col as usize));
+    let formats = NumberFormats::load(source, &sheet_name, origin)?;
+    Ok(SourceSheet {
+        name: sheet_name,
+        cells,
+        formats,
+        origin,
+    })
+}
+
+/// Where the cells of the read range land in one output file.
+struct ChunkPositions {
+    /// Read-range row -> output row; header rows keep their position.
+    rows: HashMap<usize, u32>,
+    /// Source column -> output column; `None` keeps every column in place.
+    columns: Option<HashMap<usize, u16>>,
+}
+
+impl ChunkPositions {
+    /// `rows` are the data rows (indices into the data rows) in output order, `columns` the
+    /// source columns in output order.
+    fn new(header_rows: usize, rows: &[usize], columns: Option<&[usize]>) -> Self {
+        let rows = (0..header_rows)
+            .chain(rows.iter().map(|&idx| header_rows + idx))
+            .zip(0u32..)
+            .collect();
+        let columns = columns.map(|columns| columns.iter().copied().zip(0u16..).collect());
+        Self { rows, columns }
     }
 
-    let header = rows[..header_rows].to_vec();
-    let data_rows = rows[header_rows..].to_vec();
-    let total_rows = rows.len();
+    fn row(&self, row: usize) -> Option<u32> {
+        self.rows.get(&row).copied()
+    }
 
-    let merge_ranges = extract_merge_ranges(source, &sheet_name)?;
+    /// Last output row, `None` when nothing is written.
+    fn last_row(&self) -> Option<u32> {
+        self.rows.values().max().copied()
+    }
 
-    let data_capacity = chunk_size - header_rows;
-    let mut chunks = Vec::new();
+    fn column(&self, col: usize) -> Option<u16> {
+        match &self.columns {
+            Some(columns) => columns.get(&col).copied(),
+            None => u16::try_from(col).ok(),
+        }
+    }
 
-    if data_rows.is_empty() {
-        let path = build_output_path(source, 1);
-        let chunk_merges = map_chunk_merges(&merge_ranges, header_rows, 0, 0, &header, &data_rows);
-        write_chunk(&path, &header, &[], &chunk_merges)?;
-        chunks.push(SplitChunk {
-            file_path: path,
-            total_rows: header_rows,
-            data_rows: 0,
-        });
-    } else {
-        let mut start = 0;
-        let mut index = 1;
-        while start < data_rows.len() {
-            let end = (start + data_capacity).min(data_rows.len());
-            let chunk_data = &data_rows[start..end];
-            let path = build_output_path(source, index);
-            let chunk_merges =
-                map_chunk_merges(&merge_ranges, header_rows, start, end, &header, &data_rows);
-            write_chunk(&path, &header, chunk_data, &chunk_merges)?;
-            chunks.push(SplitChunk {
-                file_path: path,
-                total_rows: header_rows + chunk_data.len(),
-                data_rows: chunk_data.len(),
-            });
-            start = end;
-            index += 1;
+    /// Output position of a cell, `None` when its row or column is not in the output.
+    fn cell(&self, row: usize, col: usize) -> Option<(u32, u16)> {
+        Some((self.row(row)?, self.column(col)?))
+    }
+
+    /// Output ranges covering the cells of `range` in this file, split where its rows or
+    /// columns are no longer adjacent. A range running past the last read row, such as a
+    /// whole column, keeps running past the file's last row.
+    fn rule_ranges(&self, range: &RuleRange) -> Vec<OutputRange> {
+        let cells = &range.cells;
+        let mut rows = runs((cells.start_row..=cells.end_row).filter_map(|row| self.row(row)));
+        if range.open_rows > 0
+            && let Some(last) = rows.last_mut()
+            && Some(last.1) == self.last_row()
+        {
+            last.1 = (last.1 as usize + range.open_rows).min(LAST_SHEET_ROW) as u32;
         }
+        let columns = runs(
+            (cells.start_col..=cells.end_col)
+                .filter_map(|col| self.column(col))
+                .map(u32::from),
+        );
+        rows.iter()
+            .flat_map(|&(first_row, last_row)| {
+                columns.iter().map(move |&(first_col, last_col)| {
+                    (first_row, first_col as u16, last_row, last_col as u16)
+                })
+            })
+            .collect()
     }
+}
 
-    Ok(SplitResult {
-        total_rows,
-        header_rows,
-        chunks,
+/// Last row of a worksheet (0-based).
+const LAST_SHEET_ROW: usize = 1_048_575;
+
+/// A range a data validation or conditional format applies to, moved to the read range.
+#[derive(Clone)]
+struct RuleRange {
+    /// The part of the range inside the read rows.
+    cells: MergeRange,
+    /// Rows the range reaches past the last read row, such as the rest of a whole column.
+    open_rows: usize,
+}
+
+/// First row, first column, last row and last column of a range in an output file.
+type OutputRange = (u32, u16, u32, u16);
+
+/// The ranges of a space-separated `sqref` list, relative to `origin` and clipped to the
+/// `total_rows` rows read; ranges outside of them are left out.
+fn parse_sqref(sqref: &str, origin: (usize, usize), total_rows: usize) -> Vec<RuleRange> {
+    sqref
+        .split_whitespace()
+        .filter_map(parse_range_ref)
+        .filter_map(|range| {
+            let end_row = range.end_row.checked_sub(origin.0)?;
+            let end_col = range.end_col.checked_sub(origin.1)?;
+            let start_row = range.start_row.saturating_sub(origin.0);
+            if start_row >= total_rows {
+                return None;
+            }
+            Some(RuleRange {
+                cells: MergeRange {
+                    start_row,
+                    end_row: end_row.min(total_rows - 1),
+                    start_col: range.start_col.saturating_sub(origin.1),
+                    end_col,
+                },
+                // A range down to the bottom of the sheet stays that way whatever the file's
+                // length.
+                open_rows: if range.end_row >= LAST_SHEET_ROW {
+                    LAST_SHEET_ROW
+                } else {
+                    end_row.saturating_sub(total_rows - 1)
+                },
+            })
+        })
+        .collect()
+}
+
+/// Consecutive runs of the given positions, as first and last position.
+fn runs(positions: impl Iterator<Item = u32>) -> Vec<(u32, u32)> {
+    let mut positions: Vec<u32> = positions.collect();
+    positions.sort_unstable();
+    positions.dedup();
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for position in positions {
+        match runs.last_mut() {
+            Some(run) if run.1 + 1 == position => run.1 = position,
+            _ => runs.push((position, position)),
+        }
+    }
+    runs
+}
+
+/// Output ranges as a space-separated `sqref` list such as `A2:A6 C2:C6`.
+fn range_list(ranges: &[OutputRange]) -> String {
+    ranges
+        .iter()
+        .map(|&(first_row, first_col, last_row, last_col)| {
+            format!(
+                "{}:{}",
+                cell_ref(first_row as usize, first_col as usize),
+                cell_ref(last_row as usize, last_col as usize)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether a formula reads cells on other sheets or through a defined name (`names`), neither
+/// of which exists in the outputs.
+fn refers_elsewhere(formula: &str, names: &[(String, String)]) -> bool {
+    let upper = formula.to_uppercase();
+    // Quoted text may contain anything.
+    let unquoted: String = upper.split('"').step_by(2).collect();
+    unquoted.contains('!')
+        || unquoted.contains("INDIRECT(")
+        || unquoted
+            .split(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '.'))
+            .any(|word| names.iter().any(|(name, _)| name.to_uppercase() == word))
+}
+
+/// The cell Excel reads the relative references of a rule's formulas from: the top-left cell
+/// of the rule's first range, as a sheet cell, with the sheet cell of the first cell read.
+#[derive(Clone, Copy)]
+struct RuleAnchor {
+    cell: (usize, usize),
+    origin: (usize, usize),
+}
+
+impl RuleAnchor {
+    fn new(sqref: &str, origin: (usize, usize)) -> Option<Self> {
+        let first = parse_range_ref(sqref.split_whitespace().next()?)?;
+        Some(Self {
+            cell: (first.start_row, first.start_col),
+            origin,
+        })
+    }
+}
+
+/// One row or column part of a reference, e.g. the `$B` or the `7` of `$B7`.
+struct ReferencePart {
+    index: usize,
+    fixed: bool,
+}
+
+/// A reference found in a formula: a cell (`B7`), a whole column (`A:C`) or a whole row
+/// (`2:5`); `end` is the index just after it.
+struct FoundReference {
+    end: usize,
+    parts: Vec<(Option<ReferencePart>, Option<ReferencePart>)>,
+}
+
+/// The cell, column range or row range starting at `start` of `chars`, if there is one.
+fn reference_at(chars: &[char], start: usize) -> Option<FoundReference> {
+    let fixed = |pos: usize| chars.get(pos) == Some(&'$');
+    let take = |pos: usize, test: fn(&char) -> bool| {
+        chars[pos.min(chars.len())..]
+            .iter()
+            .take_while(|ch| test(ch))
+            .count()
+    };
+    let column = |pos: usize| -> Option<(ReferencePart, usize)> {
+        let fixed = fixed(pos);
+        let from = pos + usize::from(fixed);
+        let len = take(from, char::is_ascii_alphabetic);
+        let label: String = chars[from..from + len].iter().collect();
+        let index = column_label_to_index(&label).filter(|&col| len <= 3 && col <= LAST_COLUMN)?;
+        Some((ReferencePart { index, fixed }, from + len))
+    };
+    let row = |pos: usize| -> Option<(ReferencePart, usize)> {
+        let fixed = fixed(pos);
+        let from = pos + usize::from(fixed);
+        let len = take(from, char::is_ascii_digit);
+        let digits: String = chars[from..from + len].iter().collect();
+        let index = digits
+            .parse::<usize>()
+            .ok()?
+            .checked_sub(1)
+            .filter(|&row| row <= LAST_SHEET_ROW)?;
+        Some((ReferencePart { index, fixed }, from + len))
+    };
+    let ends_word = |pos: usize| {
+        chars
+            .get(pos)
+            .is_none_or(|ch| !(ch.is_alphanumeric() || matches!(ch, '_' | '.' | '(' | '$')))
+    };
+
+    if let Some((col, pos)) = column(start) {
+        if let Some((row, end)) = row(pos)
+            && ends_word(end)
+        {
+            return Some(FoundReference {
+                end,
+                parts: vec![(Some(row), Some(col))],
+            });
+        }
+        if chars.get(pos) == Some(&':')
+            && let Some((last, end)) = column(pos + 1)
+            && ends_word(end)
+        {
+            return Some(FoundReference {
+                end,
+                parts: vec![(None, Some(col)), (None, Some(last))],
+            });
+        }
+        return None;
+    }
+    let (first, pos) = row(start)?;
+    if chars.get(pos) != Some(&':') {
+        return None;
+    }
+    let (last, end) = row(pos + 1)?;
+    ends_word(end).then(|| FoundReference {
+        end,
+        parts: vec![(Some(first), None), (Some(last), None)],
     })
 }
 
+/// Last column of a worksheet (0-based).
+const LAST_COLUMN: usize = 16_383;
+
+impl ChunkPositions {
+    /// `formula` of a rule anchored at `anchor` and written to `output`, with its references
+    /// moved into this file: fixed rows and columns to where their cells landed, relative rows
+    /// by as much as the rule's first row moved, and relative columns to the column the same
+    /// offset away in the source. `None` when a reference points at a cell this file does not
+    /// hold, or a relative column no longer keeps its offset across the rule's columns.
+    fn rule_formula(
+        &self,
+        formula: &str,
+        anchor: RuleAnchor,
+        ranges: &[RuleRange],
+        output: &[OutputRange],
+    ) -> Option<String> {
+        let &(out_row, out_col, ..) = output.first()?;
+        // The source column written to the first column of the output ranges.
+        let anchor_col = match &self.columns {
+            Some(columns) => *columns.iter().find(|&(_, &col)| col == out_col)?.0,
+            None => out_col as usize,
+        };
+        let origin = anchor.origin;
+        let move_row = |part: &ReferencePart| -> Option<u32> {
+            if part.fixed {
+                self.row(part.index.checked_sub(origin.0)?)
+            } else {
+                let row = out_row as isize + part.index as isize - anchor.cell.0 as isize;
+                u32::try_from(row)
+                    .ok()
+                    .filter(|&row| row as usize <= LAST_SHEET_ROW)
+            }
+        };
+        let move_column = |part: &ReferencePart| -> Option<u16> {
+            if part.fixed {
+                return self.column(part.index.checked_sub(origin.1)?);
+            }
+            let offset = part.index as isize - anchor.cell.1 as isize;
+            let source = |col: usize| usize::try_from(col as isize + offset).ok();
+            let moved = self.column(source(anchor_col)?)?;
+            // Excel keeps the output offset for every column the rule covers.
+            let shift = moved as isize - out_col as isize;
+            let consistent = ranges
+                .iter()
+                .flat_map(|range| range.cells.start_col..=range.cells.end_col)
+                .filter_map(|col| Some((col, self.column(col)?)))
+                .all(|(col, out)| {
+                    source(col).and_then(|col| self.column(col))
+                        == u16::try_from(out as isize + shift).ok()
+                });
+            consistent.then_some(moved)
+        };
+
+        let chars: Vec<char> = formula.chars().collect();
+        let mut result = String::with_capacity(formula.len());
+        let mut quoted = false;
+        let mut pos = 0;
+        while pos < chars.len() {
+            let ch = chars[pos];
+            let starts_word = pos == 0 || {
+                let before = chars[pos - 1];
+                !(before.is_alphanumeric() || matches!(before, '_' | '.' | '$'))
+            };
+            if ch == '"' {
+                quoted = !quoted;
+            } else if !quoted
+                && starts_word
+                && let Some(found) = reference_at(&chars, pos)
+            {
+                let parts = found
+                    .parts
+                    .iter()
+                    .map(|(row, col)| {
+                        let row = match row {
+                            Some(row) => Some((move_row(row)?, row.fixed)),
+                            None => None,
+                        };
+                        let col = match col {
+                            Some(col) => Some((move_column(col)?, col.fixed)),
+                            None => None,
+                        };
+                        Some((row, col))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let dollar = |fixed: bool| if fixed { "$" } else { "" };
+                let texts: Vec<String> = parts
+                    .into_iter()
+                    .map(|(row, col)| {
+                        let col = col.map(|(col, fixed)| {
+                            format!("{}{}", dollar(fixed), column_index_to_label(col as usize))
+                        });
+                        let row = row.map(|(row, fixed)| format!("{}{}", dollar(fixed), row + 1));
+                        format!("{}{}", col.unwrap_or_default(), row.unwrap_or_default())
+                    })
+                    .collect();
+                result.push_str(&texts.join(":"));
+                pos = found.end;
+                continue;
+            }
+            result.push(ch);
+            pos += 1;
+        }
+        Some(result)
+    }
+}
+
+/// What an output file carries besides its cell values, at output positions.
+#[derive(Default)]
+struct ChunkExtras {
+    /// Name of the output sheet; `None` keeps `rust_xlsxwriter`'s default.
+    sheet_name: Option<String>,
+    properties: Option<DocProperties>,
+    merges: Vec<ChunkMerge>,
+    hyperlinks: Vec<Hyperlink>,
+    notes: Vec<CellNote>,
+    images: Vec<SheetImage>,
+    validations: Vec<ChunkValidation>,
+    conditional_formats: Vec<ChunkConditionalRule>,
+    outline: ChunkOutline,
+    print_setup: PrintSetup,
+}
+
+/// What [`write_chunk`] needs to write data rows with their source types and formats.
+struct CellTypes<'a> {
+    /// Typed cells of each data row.
+    cells: Vec<&'a [Data]>,
+    /// Sheet row of each data row, to look up its number formats.
+    sheet_rows: Vec<usize>,
+    formats: &'a NumberFormats,
+    /// Source columns holding identifiers, written as text whatever their cell type.
+    text_columns: &'a [bool],
+}
+
+/// Writes one output file. Header rows are written as text; with `types`, data rows keep
+/// their numbers, dates and booleans in their source number formats, except identifier
+/// columns, which are written as text exactly as shown. Without `types` every value is text.
 fn write_chunk(
     destination: &Path,
     header_rows: &[Vec<String>],
-    data_rows: &[Vec<String>],
-    merges: &[ChunkMerge],
+    data_rows: &[&[String]],
+    types: Option<&CellTypes>,
+    columns: Option<&[usize]>,
+    extras: &ChunkExtras,
 ) -> Result<()> {
     let mut workbook = Workbook::new();
+    if let Some(properties) = &extras.properties {
+        workbook.set_properties(properties);
+    }
     let worksheet = workbook.add_worksheet();
+    if let Some(name) = &extras.sheet_name {
+        worksheet.set_name(name)?;
+    }
+    let text_format = Format::new().set_num_format("@");
+    let mut number_formats: HashMap<&str, Format> = HashMap::new();
 
-    let mut current_row: u32 = 0;
-
-    for header_row in header_rows {
-        for (col_idx, value) in header_row.iter().enumerate() {
-            worksheet.write_string(current_row, col_idx as u16, value)?;
+    // Links go first: a typed value written over a link cell keeps the link, while text
+    // cells are left as the link wrote them, in the hyperlink style.
+    let mut linked: HashSet<(u32, u16)> = HashSet::new();
+    for link in &extras.hyperlinks {
+        // The link keeps the text the cell shows.
+        let row = header_rows
+            .get(link.row)
+            .map(Vec::as_slice)
+            .or_else(|| data_rows.get(link.row - header_rows.len()).copied());
+        let source_col = columns.map_or(Some(link.col), |columns| columns.get(link.col).copied());
+        let text = row
+            .zip(source_col)
+            .and_then(|(row, col)| row.get(col))
+            .map_or("", String::as_str);
+        let mut url = Url::new(link.target.as_str());
+        if let Some(tooltip) = &link.tooltip {
+            url = url.set_tip(tooltip.as_str());
         }
-        current_row += 1;
+        worksheet.write_url_with_text(link.row as u32, link.col as u16, url, text)?;
+        linked.insert((link.row as u32, link.col as u16));
     }
 
-    for data_row in data_rows {
-        for (col_idx, value) in data_row.iter().enumerate() {
-            worksheet.write_string(current_row, col_idx as u16, value)?;
+    // Each row with its typed cells and sheet row, when known.
+    let rows: Vec<_> = header_rows
+        .iter()
+        .map(|row| (row.as_slice(), None))
+        .chain(data_rows.iter().enumerate().map(|(idx, row)| {
+            let typed =
+                types.and_then(|types| Some((*types.cells.get(idx)?, types.sheet_rows[idx])));
+            (*row, typed)
+        }))
+        .collect();
+    // Writes the value of `source_col` in output row `current_row` into column `col_idx`.
+    let mut write_cell = |worksheet: &mut Worksheet,
+                          current_row: u32,
+                          col_idx: u16,
+                          source_col: usize| {
+        let Some(&(row, typed)) = rows.get(current_row as usize) else {
+            return Ok(());
+        };
+        let Some(text) = row.get(source_col) else {
+            return Ok(());
+        };
+        let (Some(types), Some((cells, sheet_row))) = (types, typed) else {
+            if !linked.contains(&(current_row, col_idx)) {
+                worksheet.write_string(current_row, col_idx, text)?;
+            }
+            return Ok(());
+        };
+        let as_text = types.text_columns.get(source_col).copied().unwrap_or(false);
+        let code = types.formats.code(sheet_row, source_col);
+        let number = match cells.get(source_col) {
+            Some(_) if as_text => None,
+            Some(Data::Float(f)) if f.is_finite() => Some((*f, code)),
+            Some(Data::Int(i)) if i.unsigned_abs() <= MAX_EXACT_INTEGER => Some((*i as f64, code)),
+            Some(Data::DateTime(date_time)) => Some((
+                date_time.as_f64(),
+                Some(formats::date_time_code(date_time, code)),
+            )),
+            _ => None,
+        };
+        match (number, cells.get(source_col)) {
+            (Some((value, Some(code))), _) => {
+                let format = number_formats
+                    .entry(code)
+                    .or_insert_with(|| Format::new().set_num_format(code));
+                worksheet.write_number_with_format(current_row, col_idx, value, format)?;
+            }
+            (Some((value, None)), _) => {
+                worksheet.write_number(current_row, col_idx, value)?;
+            }
+            (None, Some(_)) if as_text && !text.is_empty() => {
+                worksheet.write_string_with_format(current_row, col_idx, text, &text_format)?;
+            }
+            (None, Some(Data::Bool(b))) => {
+                worksheet.write_boolean(current_row, col_idx, *b)?;
+            }
+            _ if linked.contains(&(current_row, col_idx)) => {}
+            _ => {
+                worksheet.write_string(current_row, col_idx, text)?;
+            }
+        }
+        Ok::<(), XlsxError>(())
+    };
+    for (current_row, (row, _)) in (0u32..).zip(&rows) {
+        let source_columns: Vec<usize> = match columns {
+            Some(columns) => columns.to_vec(),
+            None => (0..row.len()).collect(),
+        };
+        for (col_idx, source_col) in (0u16..).zip(source_columns) {
+            write_cell(worksheet, current_row, col_idx, source_col)?;
         }
-        current_row += 1;
     }
 
-    if !merges.is_empty() {
+    // A merge writes its first cell as text, so the typed value is written again over it.
+    if !extras.merges.is_empty() {
         let merge_format = Format::new();
-        for merge in merges {
+        for merge in &extras.merges {
             worksheet.merge_range(
                 merge.start_row,
                 merge.start_col,
                 merge.end_row,
                 merge.end_col,
-                &merge.value,
+                "",
                 &merge_format,
             )?;
+            write_cell(
+                worksheet,
+                merge.start_row,
+                merge.start_col,
+                merge.source_col,
+            )?;
         }
     }
 
+    for note in &extras.notes {
+        let mut output = Note::new(note.text.as_str()).add_author_prefix(note.author_line);
+        if let Some(author) = &note.author {
+            output = output.set_author(author.as_str());
+        }
+        worksheet.insert_note(note.row as u32, note.col as u16, &output)?;
+    }
+
+    for image in &extras.images {
+        worksheet.insert_image_with_offset(
+            image.row as u32,
+            image.col as u16,
+            &image.image,
+            image.x_offset,
+            image.y_offset,
+        )?;
+    }
+
+    for validation in &extras.validations {
+        let Some(&(first_row, first_col, last_row, last_col)) = validation.ranges.first() else {
+            continue;
+        };
+        let rule = validation
+            .rule
+            .clone()
+            .set_multi_range(range_list(&validation.ranges));
+        worksheet.add_data_validation(first_row, first_col, last_row, last_col, &rule)?;
+    }
+
+    for format in &extras.conditional_formats {
+        conditional::write_conditional_format(worksheet, format)?;
+    }
+
+    outline::write_outline(worksheet, &extras.outline)?;
+    print::write_print_setup(worksheet, &extras.print_setup)?;
+
     workbook.save(destination)?;
     Ok(())
 }
 
-fn convert_row(row: &[Data]) -> Vec<String> {
-    row.iter().map(format_cell).collect()
+/// Larger integers cannot be stored exactly as an Excel number and are written as text.
+const MAX_EXACT_INTEGER: u64 = 1 << 53;
+
+/// Converts row `row_idx` of the read range to text, showing dates in their number formats.
+fn convert_row(row: &[Data], row_idx: usize, formats: &NumberFormats) -> Vec<String> {
+    row.iter()
+        .enumerate()
+        .map(|(col, cell)| match cell {
+            Data::DateTime(dt) => formats::render_date_time(dt, formats.code(row_idx, col)),
+            _ => format_cell(cell),
+        })
+        .collect()
 }
 
 fn format_cell(value: &Data) -> String {
@@ -179,10 +1760,7 @@ fn format_cell(value: &Data) -> String {
                 "FALSE".into()
             }
         }
-        Data::DateTime(dt) => dt
-            .as_datetime()
-            .map(|date_time| date_time.format("%Y-%m-%d %H:%M:%S").to_string())
-            .unwrap_or_else(|| dt.as_f64().to_string()),
+        Data::DateTime(dt) => formats::render_date_time(dt, None),
         Data::DateTimeIso(iso) | Data::DurationIso(iso) => iso.clone(),
         Data::Error(e) => format!("错误: {e:?}"),
     }
@@ -193,7 +1771,15 @@ fn format_float(value: f64) -> String {
         return value.to_string();
     }
 
-    if value.fract() == 0.0 {
+    if value.fract() == 0.0 && value.abs() >= 1e15 {
+        // Beyond 15 significant digits an f64 shows noise where Excel shows zeros.
+        let scientific = format!("{:.14e}", value);
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+        let exponent: usize = exponent.parse().unwrap_or(0);
+        let sign = if value < 0.0 { "-" } else { "" };
+        let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+        format!("{sign}{digits:0<width$}", width = exponent + 1)
+    } else if value.fract() == 0.0 {
         format!("{:.0}", value)
     } else {
         let mut repr = format!("{value}");
@@ -224,28 +1810,171 @@ fn build_output_path(source: &Path, index: usize) -> PathBuf {
     parent.join(format!("{stem}_part{index}.xlsx"))
 }
 
-fn extract_merge_ranges(source: &Path, sheet_name: &str) -> Result<Vec<MergeRange>> {
+/// `<stem>_<key>.xlsx`, with characters that are not allowed in file names replaced.
+fn build_group_output_path(source: &Path, key: &str) -> PathBuf {
+    let parent = source
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("split");
+    let key: String = key
+        .chars()
+        .map(|ch| match ch {
+            '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            ch if ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect();
+    parent.join(format!("{stem}_{key}.xlsx"))
+}
+
+/// The merged ranges of the sheet, relative to `origin`; ranges reaching above or left of the
+/// first cell read are left out.
+fn extract_merge_ranges(
+    source: &Path,
+    sheet_name: &str,
+    origin: (usize, usize),
+) -> Result<Vec<MergeRange>> {
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(Vec::new());
+    };
+    let (_, sheet_xml) = read_sheet_xml(&mut archive, sheet_name)?;
+    Ok(parse_merge_cells(&sheet_xml)
+        .into_iter()
+        .filter_map(|merge| {
+            Some(MergeRange {
+                start_row: merge.start_row.checked_sub(origin.0)?,
+                end_row: merge.end_row - origin.0,
+                start_col: merge.start_col.checked_sub(origin.1)?,
+                end_col: merge.end_col - origin.1,
+            })
+        })
+        .collect())
+}
+
+/// Opens an `.xlsx` file as a ZIP archive; other formats give `None`.
+fn open_xlsx_archive(source: &Path) -> Result<Option<ZipArchive<File>>> {
     let extension = source
         .extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("")
         .to_ascii_lowercase();
     if extension != "xlsx" {
-        return Ok(Vec::new());
+        return Ok(None);
     }
 
     let file = File::open(source)
         .with_context(|| format!("无法以 ZIP 方式打开 Excel 文件: {}", source.display()))?;
-    let mut archive = ZipArchive::new(file)
-        .with_context(|| "无法解压 Excel 文件以读取合并单元信息".to_string())?;
+    let archive =
+        ZipArchive::new(file).with_context(|| "无法解压 Excel 文件以读取工作表信息".to_string())?;
+    Ok(Some(archive))
+}
 
-    let workbook_xml = read_zip_entry(&mut archive, "xl/workbook.xml")?;
+/// Path inside the archive and XML of the worksheet named `sheet_name`.
+fn read_sheet_xml<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    sheet_name: &str,
+) -> Result<(String, String)> {
+    let workbook_xml = read_zip_entry(archive, "xl/workbook.xml")?;
     let rel_id = find_sheet_rel_id(&workbook_xml, sheet_name)?;
-    let rels_xml = read_zip_entry(&mut archive, "xl/_rels/workbook.xml.rels")?;
+    let rels_xml = read_zip_entry(archive, "xl/_rels/workbook.xml.rels")?;
     let target = find_sheet_target(&rels_xml, &rel_id)?;
     let full_path = format!("xl/{}", target.trim_start_matches('/'));
-    let sheet_xml = read_zip_entry(&mut archive, &full_path)?;
-    Ok(parse_merge_cells(&sheet_xml))
+    let sheet_xml = read_zip_entry(archive, &full_path)?;
+    Ok((full_path, sheet_xml))
+}
+
+/// One relationship of a package part, from the part's `.rels` file.
+struct Relationship {
+    id: String,
+    /// Last segment of the relationship type, such as `hyperlink` or `comments`.
+    kind: String,
+    /// Archive path for targets inside the package, the target as written for external ones.
+    target: String,
+    external: bool,
+}
+
+/// Relationships of the part at `part_path`; a part without a `.rels` file has none.
+fn read_relationships<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    part_path: &str,
+) -> Vec<Relationship> {
+    let (dir, file) = part_path.rsplit_once('/').unwrap_or(("", part_path));
+    let Ok(rels_xml) = read_zip_entry(archive, &format!("{dir}/_rels/{file}.rels")) else {
+        return Vec::new();
+    };
+
+    let mut reader = XmlReader::from_str(&rels_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut relationships = Vec::new();
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) | Event::Empty(e) => {
+                if e.local_name().as_ref() != b"Relationship" {
+                    continue;
+                }
+                let mut id = None;
+                let mut kind = String::new();
+                let mut target = None;
+                let mut external = false;
+                for attr in e.attributes().flatten() {
+                    let Ok(value) = attr.decode_and_unescape_value(&reader) else {
+                        continue;
+                    };
+                    match attr.key.as_ref() {
+                        b"Id" => id = Some(value.into_owned()),
+                        b"Type" => kind = value.rsplit('/').next().unwrap_or("").to_string(),
+                        b"Target" => target = Some(value.into_owned()),
+                        b"TargetMode" => external = value == "External",
+                        _ => {}
+                    }
+                }
+                if let (Some(id), Some(target)) = (id, target) {
+                    let target = if external {
+                        target
+                    } else {
+                        resolve_part_path(dir, &target)
+                    };
+                    relationships.push(Relationship {
+                        id,
+                        kind,
+                        target,
+                        external,
+                    });
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    relationships
+}
+
+/// Archive path of `target` written relative to the directory `dir` (or from the package root
+/// when it starts with `/`).
+fn resolve_part_path(dir: &str, target: &str) -> String {
+    let mut segments: Vec<&str> = match target.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => dir
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect(),
+    };
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
 }
 
 fn read_zip_entry<R: Read + std::io::Seek>(
@@ -261,6 +1990,42 @@ fn read_zip_entry<R: Read + std::io::Seek>(
     Ok(contents)
 }
 
+/// Whether the workbook counts dates from 1904 (old Mac Excel) instead of 1900.
+fn uses_1904_dates<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>) -> bool {
+    let Ok(workbook_xml) = read_zip_entry(archive, "xl/workbook.xml") else {
+        return false;
+    };
+    let mut reader = XmlReader::from_str(&workbook_xml);
+    let mut buf = Vec::new();
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"workbookPr" => {
+                return e.attributes().flatten().any(|attr| {
+                    attr.key.local_name().as_ref() == b"date1904"
+                        && matches!(attr.value.as_ref(), b"1" | b"true")
+                });
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    false
+}
+
+fn read_zip_bytes<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &str,
+) -> Result<Vec<u8>> {
+    let mut file = archive
+        .by_name(path)
+        .with_context(|| format!("Excel 文件缺少 {path}"))?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .with_context(|| format!("无法读取 {path}"))?;
+    Ok(contents)
+}
+
 fn find_sheet_rel_id(workbook_xml: &str, sheet_name: &str) -> Result<String> {
     let mut reader = XmlReader::from_str(workbook_xml);
     reader.trim_text(true);
@@ -408,6 +2173,70 @@ fn parse_cell_ref(cell: &str) -> Option<(usize, usize)> {
     Some((row, col))
 }
 
+/// Finds a column by its header text (the lowest header row wins) or, failing that, by its
+/// sheet letter such as `C` or `AB`; `first_column` is the sheet column of the header's first
+/// cell. Letters outside the columns of the header are rejected, so a misspelt name such as
+/// `ID` does not silently pick an empty column.
+fn resolve_column(spec: &str, header: &[Vec<String>], first_column: usize) -> Result<usize> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return Err(anyhow!("列名不能为空"));
+    }
+
+    for row in header.iter().rev() {
+        if let Some(idx) = row.iter().position(|value| value.trim() == spec) {
+            return Ok(idx);
+        }
+    }
+
+    // Every row calamine returns spans the whole used range, so the header shows the width.
+    let width = header.iter().map(Vec::len).max();
+    if spec.len() <= 3
+        && spec.chars().all(|ch| ch.is_ascii_alphabetic())
+        && let Some(idx) = column_label_to_index(spec)
+    {
+        let Some(idx) = idx.checked_sub(first_column) else {
+            return Err(anyhow!(
+                "在表头中找不到列“{spec}”，按列字母理解也在表格的第一列 {} 左侧",
+                column_index_to_label(first_column)
+            ));
+        };
+        match width {
+            Some(width) if idx >= width => {
+                return Err(anyhow!(
+                    "在表头中找不到列“{spec}”，按列字母理解也超出了工作表的最后一列 {}",
+                    column_index_to_label(first_column + width.saturating_sub(1))
+                ));
+            }
+            _ => return Ok(idx),
+        }
+    }
+
+    Err(anyhow!("在表头中找不到列“{spec}”"))
+}
+
+fn column_index_to_label(index: usize) -> String {
+    let mut label = Vec::new();
+    let mut value = index + 1;
+    while value > 0 {
+        let rem = (value - 1) % 26;
+        label.push(b'A' + rem as u8);
+        value = (value - 1) / 26;
+    }
+    label.reverse();
+    String::from_utf8(label).unwrap_or_default()
+}
+
+/// The 1-based row number Excel shows for `row` of the read range, whose first row is sheet
+/// row `origin_row` (0-based).
+fn sheet_row_number(origin_row: usize, row: usize) -> usize {
+    origin_row + row + 1
+}
+
+fn cell_ref(row: usize, col: usize) -> String {
+    format!("{}{}", column_index_to_label(col), row + 1)
+}
+
 fn column_label_to_index(label: &str) -> Option<usize> {
     let mut value = 0usize;
     for ch in label.chars() {
@@ -423,25 +2252,70 @@ fn column_label_to_index(label: &str) -> Option<usize> {
 fn map_chunk_merges(
     merges: &[MergeRange],
     header_rows: usize,
-    data_start: usize,
-    data_end: usize,
+    chunk_rows: &[usize],
+    columns: Option<&[usize]>,
     header_data: &[Vec<String>],
     data_data: &[Vec<String>],
 ) -> Vec<ChunkMerge> {
+    if merges.is_empty() {
+        return Vec::new();
+    }
+
+    // Sheet row -> row in the output file; header rows keep their position.
+    let positions: HashMap<usize, usize> = (0..header_rows)
+        .map(|row| (row, row))
+        .chain(
+            chunk_rows
+                .iter()
+                .enumerate()
+                .map(|(pos, &idx)| (header_rows + idx, header_rows + pos)),
+        )
+        .collect();
+    let column_positions: Option<HashMap<usize, usize>> = columns.map(|columns| {
+        columns
+            .iter()
+            .enumerate()
+            .map(|(pos, &col)| (col, pos))
+            .collect()
+    });
+
     let mut result = Vec::new();
     for merge in merges {
-        if !row_in_chunk(merge.start_row, header_rows, data_start, data_end)
-            || !row_in_chunk(merge.end_row, header_rows, data_start, data_end)
-        {
+        let Some(&start_row) = positions.get(&merge.start_row) else {
+            continue;
+        };
+        // Every merged row has to land in this file, in the same order and without gaps.
+        let contiguous = (merge.start_row..=merge.end_row)
+            .enumerate()
+            .all(|(offset, row)| positions.get(&row) == Some(&(start_row + offset)));
+        if !contiguous {
             continue;
         }
-        let start_row = map_row_to_chunk(merge.start_row, header_rows, data_start);
-        let end_row = map_row_to_chunk(merge.end_row, header_rows, data_start);
-        let start_col = match u16::try_from(merge.start_col) {
+        let end_row = start_row + (merge.end_row - merge.start_row);
+
+        // With a column selection the merge shrinks to the selected part of its columns, as long
+        // as that part stays side by side in the output.
+        let (start_col, end_col) = match &column_positions {
+            Some(column_positions) => {
+                let mut kept: Vec<usize> = (merge.start_col..=merge.end_col)
+                    .filter_map(|col| column_positions.get(&col).copied())
+                    .collect();
+                kept.sort_unstable();
+                let (Some(&first), Some(&last)) = (kept.first(), kept.last()) else {
+                    continue;
+                };
+                if last - first + 1 != kept.len() || (first == last && start_row == end_row) {
+                    continue;
+                }
+                (first, last)
+            }
+            None => (merge.start_col, merge.end_col),
+        };
+        let start_col = match u16::try_from(start_col) {
             Ok(col) => col,
             Err(_) => continue,
         };
-        let end_col = match u16::try_from(merge.end_col) {
+        let end_col = match u16::try_from(end_col) {
             Ok(col) => col,
             Err(_) => continue,
         };
@@ -458,28 +2332,12 @@ fn map_chunk_merges(
             start_col,
             end_col,
             value,
+            source_col: merge.start_col,
         });
     }
     result
 }
 
-fn row_in_chunk(row: usize, header_rows: usize, data_start: usize, data_end: usize) -> bool {
-    if row < header_rows {
-        true
-    } else {
-        let data_idx = row - header_rows;
-        data_idx >= data_start && data_idx < data_end
-    }
-}
-
-fn map_row_to_chunk(row: usize, header_rows: usize, data_start: usize) -> usize {
-    if row < header_rows {
-        row
-    } else {
-        header_rows + (row - header_rows - data_start)
-    }
-}
-
 fn get_cell_value(
     header_rows: &[Vec<String>],
     data_rows: &[Vec<String>],
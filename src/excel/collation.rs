@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+/// How text values are ordered wherever the split orders by text (sort keys, group order).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Collation {
+    /// Unicode code points, the plain string order.
+    #[default]
+    CodePoint,
+    /// Chinese characters by pinyin.
+    Pinyin,
+    /// Like [`Collation::Pinyin`], but a leading surname with a special reading (单 shàn,
+    /// 曾 zēng, 解 xiè, 尉迟 yùchí, ...) sorts by that reading. Meant for name columns.
+    PinyinSurname,
+    /// Chinese characters by stroke count, then by stroke order.
+    Strokes,
+}
+
+/// Surnames read differently from the character's common reading, with characters that
+/// carry the surname reading. Compound surnames come first so they win over their first
+/// character.
+const SURNAME_READINGS: &[(&str, &str)] = &[
+    ("尉迟", "玉迟"),
+    ("万俟", "莫其"),
+    ("单于", "蝉于"),
+    ("单", "善"),
+    ("曾", "增"),
+    ("解", "谢"),
+    ("仇", "球"),
+    ("区", "欧"),
+    ("查", "渣"),
+    ("盖", "葛"),
+    ("乐", "岳"),
+    ("员", "运"),
+    ("翟", "宅"),
+    ("重", "虫"),
+    ("种", "虫"),
+    ("句", "沟"),
+    ("繁", "婆"),
+    ("秘", "必"),
+    ("折", "蛇"),
+    ("召", "邵"),
+    ("隗", "伟"),
+    ("冼", "显"),
+    ("柏", "百"),
+    ("薄", "博"),
+    ("能", "奈"),
+];
+
+impl Collation {
+    pub const ALL: [Collation; 4] = [
+        Collation::CodePoint,
+        Collation::Pinyin,
+        Collation::PinyinSurname,
+        Collation::Strokes,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Collation::CodePoint => "默认",
+            Collation::Pinyin => "拼音",
+            Collation::PinyinSurname => "拼音（姓氏读音）",
+            Collation::Strokes => "笔画",
+        }
+    }
+
+    /// Compares two texts. Other characters come before Chinese characters, and Chinese
+    /// characters missing from the tables come last. Texts that weigh the same (a surname and a
+    /// character sharing its reading) fall back to code point order, so the result is total.
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        let table = match self {
+            Collation::CodePoint => return a.cmp(b),
+            Collation::Pinyin | Collation::PinyinSurname => pinyin_weights(),
+            Collation::Strokes => stroke_weights(),
+        };
+        let (a_text, b_text) = if self == Collation::PinyinSurname {
+            (surname_reading(a), surname_reading(b))
+        } else {
+            (Cow::Borrowed(a), Cow::Borrowed(b))
+        };
+        let weigh = |ch: char| match table.get(&ch) {
+            Some(&weight) => (1, weight),
+            None if is_han(ch) => (2, ch as u32),
+            None => (0, ch as u32),
+        };
+        a_text
+            .chars()
+            .map(weigh)
+            .cmp(b_text.chars().map(weigh))
+            .then_with(|| a.cmp(b))
+    }
+}
+
+fn surname_reading(text: &str) -> Cow<'_, str> {
+    SURNAME_READINGS
+        .iter()
+        .find_map(|(surname, reading)| {
+            text.strip_prefix(surname)
+                .map(|rest| Cow::Owned(format!("{reading}{rest}")))
+        })
+        .unwrap_or(Cow::Borrowed(text))
+}
+
+fn is_han(ch: char) -> bool {
+    matches!(ch, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{3134F}')
+}
+
+fn pinyin_weights() -> &'static HashMap<char, u32> {
+    static WEIGHTS: OnceLock<HashMap<char, u32>> = OnceLock::new();
+    WEIGHTS.get_or_init(|| load_weights(include_str!("collation/pinyin.txt")))
+}
+
+fn stroke_weights() -> &'static HashMap<char, u32> {
+    static WEIGHTS: OnceLock<HashMap<char, u32>> = OnceLock::new();
+    WEIGHTS.get_or_init(|| load_weights(include_str!("collation/strokes.txt")))
+}
+
+/// Weighs every character by its position in `data`, skipping `#` comment lines.
+fn load_weights(data: &str) -> HashMap<char, u32> {
+    data.lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(str::chars)
+        .zip(0..)
+        .collect()
+}
@@ -0,0 +1,190 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use super::{ChunkPlan, column_index_to_label, read_first_sheet_cells, resolve_column};
+
+/// How the non-key columns are divided in a vertical split.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ColumnGroups {
+    /// The remaining columns from left to right, `columns` per file.
+    PerFile { columns: usize },
+    /// One file per listed group, e.g. `["C-M", "N-Z"]` or `["金额, 数量"]`.
+    Explicit { groups: Vec<String> },
+}
+
+/// One column of the output files, in output order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputColumn {
+    /// Header text or column letter in the source.
+    pub source: String,
+    /// Replaces the header text in the lowest header row; `None` keeps it.
+    pub header: Option<String>,
+}
+
+/// A column of the source sheet as offered for selection: its letter and header text.
+pub struct SourceColumn {
+    pub letter: String,
+    pub header: String,
+}
+
+/// Lists the columns of the first worksheet with their sheet letter and the text of their
+/// lowest header row.
+pub fn list_columns(source: &Path, header_rows: usize) -> Result<Vec<SourceColumn>> {
+    let sheet = read_first_sheet_cells(source)?;
+    let rows = sheet.text_rows();
+    let header = &rows[..header_rows.min(rows.len())];
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    Ok((0..width)
+        .map(|col| SourceColumn {
+            letter: column_index_to_label(sheet.origin.1 + col),
+            header: header
+                .iter()
+                .rev()
+                .filter_map(|row| row.get(col))
+                .map(|value| value.trim())
+                .find(|value| !value.is_empty())
+                .unwrap_or("")
+                .to_string(),
+        })
+        .collect())
+}
+
+/// Resolves the output column selection into source columns in output order and the header
+/// rows to write, with the renamed headers applied.
+pub fn resolve_output_columns(
+    columns: &[OutputColumn],
+    header: &[Vec<String>],
+    first_column: usize,
+) -> Result<(Vec<usize>, Vec<Vec<String>>)> {
+    let mut selected = Vec::with_capacity(columns.len());
+    let mut output_header = header.to_vec();
+    for column in columns {
+        let col = resolve_column(&column.source, header, first_column)?;
+        if selected.contains(&col) {
+            return Err(anyhow!("输出列“{}”重复", column.source.trim()));
+        }
+        selected.push(col);
+
+        let rename = column.header.as_deref().map(str::trim).unwrap_or("");
+        if let Some(row) = output_header.last_mut()
+            && !rename.is_empty()
+        {
+            if row.len() <= col {
+                row.resize(col + 1, String::new());
+            }
+            row[col] = rename.to_string();
+        }
+    }
+    if selected.is_empty() {
+        return Err(anyhow!("请至少选择一个输出列"));
+    }
+    Ok((selected, output_header))
+}
+
+/// Resolves a comma-separated list of columns, where each item is a header text, a letter or
+/// a range of either (`A-M`, `C:F`, `开始日期-结束日期`). Letters are sheet columns; the header's
+/// first cell sits in `first_column`. Duplicates keep their first position.
+pub fn resolve_column_list(
+    spec: &str,
+    header: &[Vec<String>],
+    first_column: usize,
+) -> Result<Vec<usize>> {
+    let mut columns = Vec::new();
+    for item in spec
+        .split([',', '，', '、'])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        let resolved: Vec<usize> = match resolve_column(item, header, first_column) {
+            Ok(col) => vec![col],
+            Err(err) => {
+                let Some((start, end)) = item.split_once(['-', ':', '：']) else {
+                    return Err(err);
+                };
+                let start = resolve_column(start, header, first_column)?;
+                let end = resolve_column(end, header, first_column)?;
+                if start > end {
+                    return Err(anyhow!("列范围“{item}”的起始列在结束列之后"));
+                }
+                (start..=end).collect()
+            }
+        };
+        for col in resolved {
+            if !columns.contains(&col) {
+                columns.push(col);
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// Builds one plan per column group. Every plan keeps all data rows and lists the key columns
+/// first, followed by the group's own columns.
+pub fn plan_column_groups(
+    key_columns: &str,
+    groups: &ColumnGroups,
+    header: &[Vec<String>],
+    first_column: usize,
+    width: usize,
+    data_len: usize,
+) -> Result<Vec<ChunkPlan>> {
+    let keys = resolve_column_list(key_columns, header, first_column)?;
+
+    let group_columns: Vec<Vec<usize>> = match groups {
+        ColumnGroups::PerFile { columns } => (0..width)
+            .filter(|col| !keys.contains(col))
+            .collect::<Vec<_>>()
+            .chunks(*columns)
+            .map(<[usize]>::to_vec)
+            .collect(),
+        ColumnGroups::Explicit { groups } => groups
+            .iter()
+            .map(|group| {
+                let columns = resolve_column_list(group, header, first_column)?;
+                Ok(columns
+                    .into_iter()
+                    .filter(|col| !keys.contains(col))
+                    .collect())
+            })
+            .collect::<Result<_>>()?,
+    };
+
+    if group_columns.iter().all(Vec::is_empty) {
+        return Err(anyhow!("除关键列外没有可以拆分的列"));
+    }
+
+    Ok(group_columns
+        .into_iter()
+        .filter(|columns| !columns.is_empty())
+        .map(|columns| ChunkPlan {
+            rows: (0..data_len).collect(),
+            group_key: Some(describe_columns(&columns, first_column)),
+            columns: Some(keys.iter().copied().chain(columns).collect()),
+        })
+        .collect())
+}
+
+/// `C-M` for a contiguous run of columns, otherwise the letters joined by commas. The letters
+/// are sheet columns; the first column read sits in `first_column`.
+fn describe_columns(columns: &[usize], first_column: usize) -> String {
+    let label = |col: usize| column_index_to_label(first_column + col);
+    let first = columns[0];
+    let last = columns[columns.len() - 1];
+    let contiguous = columns
+        .iter()
+        .enumerate()
+        .all(|(offset, &col)| col == first + offset);
+    if columns.len() == 1 {
+        label(first)
+    } else if contiguous {
+        format!("{}-{}", label(first), label(last))
+    } else {
+        columns
+            .iter()
+            .map(|&col| label(col))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+
+use anyhow::Result;
+use quick_xml::{Reader as XmlReader, events::Event};
+
+use super::{
+    ChunkPositions, open_xlsx_archive, parse_cell_ref, read_relationships, read_sheet_xml,
+    read_zip_entry,
+};
+
+/// Excel's limit for a note author.
+const MAX_AUTHOR_CHARS: usize = 52;
+
+/// A note (legacy comment) on one cell.
+#[derive(Debug, Clone)]
+pub struct CellNote {
+    pub row: usize,
+    pub col: usize,
+    pub author: Option<String>,
+    /// The note text without the `author:` line Excel puts in front of it.
+    pub text: String,
+    /// Whether the note showed that line; the output adds it back.
+    pub author_line: bool,
+}
+
+/// Reads the notes of `sheet_name` from the comments part the sheet's relationships point to.
+/// Positions are relative to `origin`, the sheet row and column of the first cell calamine
+/// returned. Threaded comments of newer Excel versions are read through the plain note Excel
+/// keeps next to each of them.
+pub fn extract_notes(
+    source: &Path,
+    sheet_name: &str,
+    origin: (usize, usize),
+) -> Result<Vec<CellNote>> {
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(Vec::new());
+    };
+    let (sheet_path, _) = read_sheet_xml(&mut archive, sheet_name)?;
+    let Some(comments_path) = read_relationships(&mut archive, &sheet_path)
+        .into_iter()
+        .find(|relationship| relationship.kind == "comments" && !relationship.external)
+        .map(|relationship| relationship.target)
+    else {
+        return Ok(Vec::new());
+    };
+    let comments_xml = read_zip_entry(&mut archive, &comments_path)?;
+
+    Ok(parse_comments(&comments_xml)
+        .into_iter()
+        .filter_map(|(reference, author, text)| {
+            let (row, col) = parse_cell_ref(&reference)?;
+            let row = row.checked_sub(origin.0)?;
+            let col = col.checked_sub(origin.1)?;
+            let stripped = author
+                .as_deref()
+                .and_then(|author| strip_author_line(&text, author));
+            Some(CellNote {
+                row,
+                col,
+                author: author.map(|author| author.chars().take(MAX_AUTHOR_CHARS).collect()),
+                author_line: stripped.is_some(),
+                text: stripped.unwrap_or(text),
+            })
+        })
+        .collect())
+}
+
+/// The notes of one output file at their positions in it.
+pub fn map_chunk_notes(notes: &[CellNote], positions: &ChunkPositions) -> Vec<CellNote> {
+    notes
+        .iter()
+        .filter_map(|note| {
+            let (row, col) = positions.cell(note.row, note.col)?;
+            Some(CellNote {
+                row: row as usize,
+                col: col as usize,
+                ..note.clone()
+            })
+        })
+        .collect()
+}
+
+/// Cell reference, author and plain text of every `<comment>`.
+fn parse_comments(comments_xml: &str) -> Vec<(String, Option<String>, String)> {
+    let mut reader = XmlReader::from_str(comments_xml);
+    let mut buf = Vec::new();
+    let mut authors = Vec::new();
+    let mut comments = Vec::new();
+    // The comment being read: reference, author index and text so far.
+    let mut current: Option<(String, Option<usize>, String)> = None;
+    let mut in_author = false;
+    let mut in_text = false;
+    // Phonetic runs (`<rPh>`) repeat the reading of East Asian text and are not shown.
+    let mut in_phonetic = false;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"author" => {
+                    in_author = true;
+                    authors.push(String::new());
+                }
+                b"comment" => {
+                    let mut reference = String::new();
+                    let mut author = None;
+                    for attr in e.attributes().flatten() {
+                        let Ok(value) = attr.decode_and_unescape_value(&reader) else {
+                            continue;
+                        };
+                        match attr.key.as_ref() {
+                            b"ref" => reference = value.into_owned(),
+                            b"authorId" => author = value.trim().parse().ok(),
+                            _ => {}
+                        }
+                    }
+                    current = Some((reference, author, String::new()));
+                }
+                b"t" => in_text = true,
+                b"rPh" => in_phonetic = true,
+                _ => {}
+            },
+            Event::Empty(e) if e.local_name().as_ref() == b"author" => authors.push(String::new()),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"author" => in_author = false,
+                b"t" => in_text = false,
+                b"rPh" => in_phonetic = false,
+                b"comment" => {
+                    if let Some((reference, author, text)) = current.take() {
+                        let author = author
+                            .and_then(|idx| authors.get(idx).cloned())
+                            .filter(|author: &String| !author.trim().is_empty());
+                        comments.push((reference, author, text));
+                    }
+                }
+                _ => {}
+            },
+            Event::Text(text) => {
+                let Ok(text) = text.unescape() else {
+                    continue;
+                };
+                if in_author {
+                    if let Some(author) = authors.last_mut() {
+                        author.push_str(&text);
+                    }
+                } else if in_text
+                    && !in_phonetic
+                    && let Some((_, _, note)) = current.as_mut()
+                {
+                    note.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    comments
+}
+
+/// Excel starts a note with a bold `author:` line; returns the text after it, if present.
+fn strip_author_line(text: &str, author: &str) -> Option<String> {
+    let rest = text.strip_prefix(author)?.strip_prefix(':')?;
+    Some(rest.trim_start_matches(['\r', '\n']).to_string())
+}
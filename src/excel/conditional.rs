@@ -0,0 +1,973 @@
+use std::path::Path;
+
+use anyhow::Result;
+use calamine::{Reader, open_workbook_auto};
+use quick_xml::events::BytesStart;
+use quick_xml::{Reader as XmlReader, events::Event};
+use rust_xlsxwriter::{
+    Color, ConditionalFormat2ColorScale, ConditionalFormat3ColorScale, ConditionalFormatAverage,
+    ConditionalFormatAverageRule, ConditionalFormatBlank, ConditionalFormatCell,
+    ConditionalFormatCellRule, ConditionalFormatCustomIcon, ConditionalFormatDataBar,
+    ConditionalFormatDate, ConditionalFormatDateRule, ConditionalFormatDuplicate,
+    ConditionalFormatError, ConditionalFormatFormula, ConditionalFormatIconSet,
+    ConditionalFormatIconType, ConditionalFormatText, ConditionalFormatTextRule,
+    ConditionalFormatTop, ConditionalFormatTopRule, ConditionalFormatType, ConditionalFormatValue,
+    Format, FormatBorder, FormatUnderline, Formula, Worksheet,
+};
+
+use super::{
+    ChunkPositions, OutputRange, RuleAnchor, RuleRange, open_xlsx_archive, parse_sqref, range_list,
+    read_relationships, read_sheet_xml, read_zip_entry, refers_elsewhere,
+};
+
+/// A conditional format rule and the cells it applies to. The rule is kept as read, so every
+/// output gets the references of its formulas moved along with its cells.
+pub struct ConditionalRule {
+    entry: RuleEntry,
+    format: Format,
+    sqref: String,
+    anchor: RuleAnchor,
+    ranges: Vec<RuleRange>,
+}
+
+/// A conditional format rule at its ranges in one output file.
+pub struct ChunkConditionalRule {
+    rule: Rule,
+    stop_if_true: bool,
+    ranges: Vec<OutputRange>,
+}
+
+/// The rule types `rust_xlsxwriter` can write.
+#[derive(Clone)]
+enum Rule {
+    Cell(ConditionalFormatCell),
+    Formula(ConditionalFormatFormula),
+    Text(ConditionalFormatText),
+    Blank(ConditionalFormatBlank),
+    Error(ConditionalFormatError),
+    Duplicate(ConditionalFormatDuplicate),
+    Top(ConditionalFormatTop),
+    Average(ConditionalFormatAverage),
+    Date(ConditionalFormatDate),
+    TwoColorScale(ConditionalFormat2ColorScale),
+    ThreeColorScale(ConditionalFormat3ColorScale),
+    DataBar(ConditionalFormatDataBar),
+    IconSet(ConditionalFormatIconSet),
+}
+
+/// Reads the conditional formats of `sheet_name` with the differential styles (`dxf`) of
+/// styles.xml they show. Positions are relative to `origin`, the sheet row and column of the
+/// first cell calamine returned; `total_rows` is the number of rows read, the first
+/// Rules that cannot be written again, such as those reading other sheets or only known to
+/// newer Excel versions, are dropped with a warning. The rules come back in priority order.
+pub fn extract_conditional_formats(
+    source: &Path,
+    sheet_name: &str,
+    origin: (usize, usize),
+    total_rows: usize,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<ConditionalRule>> {
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(Vec::new());
+    };
+    let (_, sheet_xml) = read_sheet_xml(&mut archive, sheet_name)?;
+    if !sheet_xml.contains("conditionalFormatting") || total_rows == 0 {
+        return Ok(Vec::new());
+    }
+    let palette = read_relationships(&mut archive, "xl/workbook.xml")
+        .into_iter()
+        .find(|relationship| relationship.kind == "theme" && !relationship.external)
+        .and_then(|relationship| read_zip_entry(&mut archive, &relationship.target).ok())
+        .map(|theme_xml| parse_theme_palette(&theme_xml))
+        .unwrap_or_default();
+    let dxfs = read_zip_entry(&mut archive, "xl/styles.xml")
+        .map(|styles_xml| parse_dxfs(&styles_xml, &palette))
+        .unwrap_or_default();
+    let names = open_workbook_auto(source)?.defined_names().to_vec();
+    let formattings = parse_conditional_formats(&sheet_xml, &palette);
+
+    // Rules of the extension list that only add Excel 2010 options to a rule read above.
+    let extended: Vec<&str> = formattings
+        .iter()
+        .flat_map(|formatting| &formatting.rules)
+        .filter_map(|rule| rule.ext_id.as_deref())
+        .collect();
+    let mut rules = Vec::new();
+    let mut skipped = Vec::new();
+    for formatting in &formattings {
+        let ranges = parse_sqref(&formatting.sqref, origin, total_rows);
+        let Some(anchor) = RuleAnchor::new(&formatting.sqref, origin) else {
+            continue;
+        };
+        if ranges.is_empty() {
+            continue;
+        }
+        for entry in &formatting.rules {
+            if formatting.extension {
+                if !entry.id.as_deref().is_some_and(|id| extended.contains(&id)) {
+                    skipped.push(format!("{} 的规则只有新版 Excel 能识别", formatting.sqref));
+                }
+                continue;
+            }
+            if let Some(formula) = formulas(entry).find(|formula| refers_elsewhere(formula, &names))
+            {
+                skipped.push(format!(
+                    "{} 的条件“{formula}”引用了其他工作表或名称",
+                    formatting.sqref
+                ));
+                continue;
+            }
+            let format = entry
+                .dxf_id
+                .and_then(|id| dxfs.get(id))
+                .cloned()
+                .unwrap_or_default();
+            match build_rule(entry, format.clone()) {
+                Ok(_) => rules.push(ConditionalRule {
+                    entry: entry.clone(),
+                    format,
+                    sqref: formatting.sqref.clone(),
+                    anchor,
+                    ranges: ranges.clone(),
+                }),
+                Err(reason) => skipped.push(format!("{} {reason}", formatting.sqref)),
+            }
+        }
+    }
+    if let Some(example) = skipped.first() {
+        warnings.push(format!(
+            "有 {} 条条件格式规则无法在输出文件中保留（如 {example}）",
+            skipped.len()
+        ));
+    }
+    rules.sort_by_key(|rule| rule.entry.priority);
+    Ok(rules)
+}
+
+/// The conditional formats of one output file, with their ranges and the references of their
+/// formulas moved along with their cells. Rules whose formulas refer to cells the file does
+/// not hold are left out and described in `dropped`.
+pub fn map_chunk_conditional_formats(
+    rules: &[ConditionalRule],
+    positions: &ChunkPositions,
+    dropped: &mut Vec<String>,
+) -> Vec<ChunkConditionalRule> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let ranges: Vec<OutputRange> = rule
+                .ranges
+                .iter()
+                .flat_map(|range| positions.rule_ranges(range))
+                .collect();
+            if ranges.is_empty() {
+                return None;
+            }
+            let moved = |formula: &String| {
+                positions.rule_formula(formula, rule.anchor, &rule.ranges, &ranges)
+            };
+            let mut entry = rule.entry.clone();
+            let built = (|| {
+                for formula in &mut entry.formulas {
+                    *formula = moved(formula)?;
+                }
+                for threshold in &mut entry.thresholds {
+                    if let Some(value) = &threshold.value {
+                        threshold.value = Some(moved(value)?);
+                    }
+                }
+                build_rule(&entry, rule.format.clone()).ok()
+            })();
+            match built {
+                Some(built) => Some(ChunkConditionalRule {
+                    rule: built,
+                    stop_if_true: entry.stop_if_true,
+                    ranges,
+                }),
+                None => {
+                    dropped.push(format!("条件格式 {}", rule.sqref));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Adds one conditional format to an output worksheet.
+pub fn write_conditional_format(
+    worksheet: &mut Worksheet,
+    format: &ChunkConditionalRule,
+) -> Result<()> {
+    let Some(&first) = format.ranges.first() else {
+        return Ok(());
+    };
+    let (first_row, first_col, last_row, last_col) = first;
+    let ranges = range_list(&format.ranges);
+    // Every rule type is its own struct with its own setters.
+    macro_rules! add {
+        ($rule:expr) => {
+            worksheet.add_conditional_format(
+                first_row,
+                first_col,
+                last_row,
+                last_col,
+                &$rule
+                    .clone()
+                    .set_multi_range(&ranges)
+                    .set_stop_if_true(format.stop_if_true),
+            )
+        };
+    }
+    match &format.rule {
+        Rule::Cell(rule) => add!(rule),
+        Rule::Formula(rule) => add!(rule),
+        Rule::Text(rule) => add!(rule),
+        Rule::Blank(rule) => add!(rule),
+        Rule::Error(rule) => add!(rule),
+        Rule::Duplicate(rule) => add!(rule),
+        Rule::Top(rule) => add!(rule),
+        Rule::Average(rule) => add!(rule),
+        Rule::Date(rule) => add!(rule),
+        Rule::TwoColorScale(rule) => add!(rule),
+        Rule::ThreeColorScale(rule) => add!(rule),
+        Rule::DataBar(rule) => add!(rule),
+        Rule::IconSet(rule) => add!(rule),
+    }?;
+    Ok(())
+}
+
+/// One `<conditionalFormatting>` element of the sheet XML.
+struct FormattingEntry {
+    sqref: String,
+    /// Read from the sheet's extension list, where Excel 2010 keeps its additions.
+    extension: bool,
+    rules: Vec<RuleEntry>,
+}
+
+/// One `<cfRule>` element.
+#[derive(Clone, Default)]
+struct RuleEntry {
+    kind: String,
+    dxf_id: Option<usize>,
+    priority: u32,
+    operator: String,
+    stop_if_true: bool,
+    text: Option<String>,
+    time_period: Option<String>,
+    rank: Option<u16>,
+    percent: bool,
+    bottom: bool,
+    above_average: bool,
+    equal_average: bool,
+    std_dev: Option<u8>,
+    formulas: Vec<String>,
+    /// Thresholds (`<cfvo>`) of a color scale, data bar or icon set.
+    thresholds: Vec<Threshold>,
+    colors: Vec<Option<u32>>,
+    icon_set: Option<String>,
+    show_value: bool,
+    reverse: bool,
+    /// Own id of a rule of the extension list.
+    id: Option<String>,
+    /// Id of the extension-list rule that extends this one.
+    ext_id: Option<String>,
+}
+
+#[derive(Clone)]
+struct Threshold {
+    kind: String,
+    value: Option<String>,
+    greater_than: bool,
+}
+
+/// The element whose text is being read.
+#[derive(Clone, Copy)]
+enum TextField {
+    Formula,
+    Sqref,
+    ExtId,
+}
+
+fn parse_conditional_formats(sheet_xml: &str, palette: &[u32]) -> Vec<FormattingEntry> {
+    let mut reader = XmlReader::from_str(sheet_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut formattings = Vec::new();
+    let mut formatting: Option<FormattingEntry> = None;
+    let mut rule: Option<RuleEntry> = None;
+    let mut field = None;
+    let mut ext_depth = 0usize;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"extLst" => ext_depth += 1,
+                b"conditionalFormatting" => {
+                    formatting = Some(FormattingEntry {
+                        sqref: attribute(&e, &reader, b"sqref").unwrap_or_default(),
+                        extension: ext_depth > 0,
+                        rules: Vec::new(),
+                    });
+                }
+                b"cfRule" if formatting.is_some() => rule = Some(read_rule(&e, &reader)),
+                b"formula" | b"f" if rule.is_some() => {
+                    if let Some(rule) = rule.as_mut() {
+                        rule.formulas.push(String::new());
+                    }
+                    field = Some(TextField::Formula);
+                }
+                b"sqref" => field = Some(TextField::Sqref),
+                b"id" if rule.is_some() => field = Some(TextField::ExtId),
+                name => read_rule_part(name, &e, &reader, rule.as_mut(), palette),
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"cfRule" => {
+                    if let Some(formatting) = formatting.as_mut() {
+                        formatting.rules.push(read_rule(&e, &reader));
+                    }
+                }
+                name => read_rule_part(name, &e, &reader, rule.as_mut(), palette),
+            },
+            Event::End(e) => match e.local_name().as_ref() {
+                b"extLst" => ext_depth = ext_depth.saturating_sub(1),
+                b"formula" | b"f" | b"sqref" | b"id" => field = None,
+                b"cfRule" => {
+                    if let (Some(formatting), Some(rule)) = (formatting.as_mut(), rule.take()) {
+                        formatting.rules.push(rule);
+                    }
+                }
+                b"conditionalFormatting" => formattings.extend(formatting.take()),
+                _ => {}
+            },
+            Event::Text(text) => {
+                let (Some(field), Ok(text)) = (field, text.unescape()) else {
+                    continue;
+                };
+                match field {
+                    TextField::Formula => {
+                        if let Some(formula) =
+                            rule.as_mut().and_then(|rule| rule.formulas.last_mut())
+                        {
+                            formula.push_str(&text);
+                        }
+                    }
+                    TextField::Sqref => {
+                        if let Some(formatting) = formatting.as_mut() {
+                            formatting.sqref.push_str(&text);
+                        }
+                    }
+                    TextField::ExtId => {
+                        if let Some(rule) = rule.as_mut() {
+                            rule.ext_id.get_or_insert_with(String::new).push_str(&text);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    formattings
+}
+
+fn read_rule(e: &BytesStart, reader: &XmlReader<&[u8]>) -> RuleEntry {
+    let mut rule = RuleEntry {
+        above_average: true,
+        show_value: true,
+        ..RuleEntry::default()
+    };
+    for attr in e.attributes().flatten() {
+        let Ok(value) = attr.decode_and_unescape_value(reader) else {
+            continue;
+        };
+        let value = value.into_owned();
+        let flag = value == "1" || value == "true";
+        match attr.key.as_ref() {
+            b"type" => rule.kind = value,
+            b"dxfId" => rule.dxf_id = value.parse().ok(),
+            b"priority" => rule.priority = value.parse().unwrap_or(u32::MAX),
+            b"operator" => rule.operator = value,
+            b"stopIfTrue" => rule.stop_if_true = flag,
+            b"text" => rule.text = Some(value),
+            b"timePeriod" => rule.time_period = Some(value),
+            b"rank" => rule.rank = value.parse().ok(),
+            b"percent" => rule.percent = flag,
+            b"bottom" => rule.bottom = flag,
+            b"aboveAverage" => rule.above_average = flag,
+            b"equalAverage" => rule.equal_average = flag,
+            b"stdDev" => rule.std_dev = value.parse().ok(),
+            b"id" => rule.id = Some(value),
+            _ => {}
+        }
+    }
+    rule
+}
+
+/// Reads the thresholds, colors and icon set options inside a `<cfRule>`.
+fn read_rule_part(
+    name: &[u8],
+    e: &BytesStart,
+    reader: &XmlReader<&[u8]>,
+    rule: Option<&mut RuleEntry>,
+    palette: &[u32],
+) {
+    let Some(rule) = rule else {
+        return;
+    };
+    match name {
+        b"cfvo" => rule.thresholds.push(Threshold {
+            kind: attribute(e, reader, b"type").unwrap_or_default(),
+            value: attribute(e, reader, b"val"),
+            greater_than: attribute(e, reader, b"gte").is_some_and(|gte| gte == "0"),
+        }),
+        b"color" => rule.colors.push(read_color(e, reader, palette)),
+        b"iconSet" | b"dataBar" => {
+            if name == b"iconSet" {
+                rule.icon_set = attribute(e, reader, b"iconSet");
+                rule.reverse = attribute(e, reader, b"reverse").is_some_and(|v| v == "1");
+            }
+            rule.show_value = attribute(e, reader, b"showValue").is_none_or(|v| v != "0");
+        }
+        _ => {}
+    }
+}
+
+/// The formulas of a rule, its thresholds' values included.
+fn formulas(entry: &RuleEntry) -> impl Iterator<Item = &String> {
+    entry.formulas.iter().chain(
+        entry
+            .thresholds
+            .iter()
+            .filter_map(|threshold| threshold.value.as_ref()),
+    )
+}
+
+/// The rule `rust_xlsxwriter` writes for one source rule in `format`, or why there is none.
+fn build_rule(entry: &RuleEntry, format: Format) -> Result<Rule, String> {
+    let formula = |idx: usize| -> Option<ConditionalFormatValue> {
+        entry
+            .formulas
+            .get(idx)
+            .map(|f| Formula::new(f.trim()).into())
+    };
+    let text = || entry.text.clone().ok_or("的文本条件为空");
+
+    Ok(match entry.kind.as_str() {
+        "cellIs" => {
+            let first = formula(0).ok_or("的条件不完整")?;
+            let rule = match entry.operator.as_str() {
+                "equal" => ConditionalFormatCellRule::EqualTo(first),
+                "notEqual" => ConditionalFormatCellRule::NotEqualTo(first),
+                "greaterThan" => ConditionalFormatCellRule::GreaterThan(first),
+                "greaterThanOrEqual" => ConditionalFormatCellRule::GreaterThanOrEqualTo(first),
+                "lessThan" => ConditionalFormatCellRule::LessThan(first),
+                "lessThanOrEqual" => ConditionalFormatCellRule::LessThanOrEqualTo(first),
+                "notBetween" => {
+                    ConditionalFormatCellRule::NotBetween(first, formula(1).ok_or("的条件不完整")?)
+                }
+                _ => ConditionalFormatCellRule::Between(first, formula(1).ok_or("的条件不完整")?),
+            };
+            Rule::Cell(
+                ConditionalFormatCell::new()
+                    .set_rule(rule)
+                    .set_format(format),
+            )
+        }
+        "expression" => {
+            let rule = entry.formulas.first().ok_or("的公式为空")?;
+            Rule::Formula(
+                ConditionalFormatFormula::new()
+                    .set_rule(Formula::new(rule.trim()))
+                    .set_format(format),
+            )
+        }
+        "containsText" | "notContainsText" | "beginsWith" | "endsWith" => {
+            let rule = match entry.kind.as_str() {
+                "containsText" => ConditionalFormatTextRule::Contains(text()?),
+                "notContainsText" => ConditionalFormatTextRule::DoesNotContain(text()?),
+                "beginsWith" => ConditionalFormatTextRule::BeginsWith(text()?),
+                _ => ConditionalFormatTextRule::EndsWith(text()?),
+            };
+            Rule::Text(
+                ConditionalFormatText::new()
+                    .set_rule(rule)
+                    .set_format(format),
+            )
+        }
+        "containsBlanks" | "notContainsBlanks" => {
+            let rule = ConditionalFormatBlank::new().set_format(format);
+            Rule::Blank(match entry.kind.as_str() {
+                "notContainsBlanks" => rule.invert(),
+                _ => rule,
+            })
+        }
+        "containsErrors" | "notContainsErrors" => {
+            let rule = ConditionalFormatError::new().set_format(format);
+            Rule::Error(match entry.kind.as_str() {
+                "notContainsErrors" => rule.invert(),
+                _ => rule,
+            })
+        }
+        "duplicateValues" | "uniqueValues" => {
+            let rule = ConditionalFormatDuplicate::new().set_format(format);
+            Rule::Duplicate(match entry.kind.as_str() {
+                "uniqueValues" => rule.invert(),
+                _ => rule,
+            })
+        }
+        "top10" => {
+            let rank = entry.rank.unwrap_or(10);
+            let rule = match (entry.bottom, entry.percent) {
+                (false, false) => ConditionalFormatTopRule::Top(rank),
+                (true, false) => ConditionalFormatTopRule::Bottom(rank),
+                (false, true) => ConditionalFormatTopRule::TopPercent(rank),
+                (true, true) => ConditionalFormatTopRule::BottomPercent(rank),
+            };
+            Rule::Top(
+                ConditionalFormatTop::new()
+                    .set_rule(rule)
+                    .set_format(format),
+            )
+        }
+        "aboveAverage" => {
+            let above = entry.above_average;
+            let rule = match (entry.std_dev, above) {
+                (Some(1), true) => ConditionalFormatAverageRule::OneStandardDeviationAbove,
+                (Some(1), false) => ConditionalFormatAverageRule::OneStandardDeviationBelow,
+                (Some(2), true) => ConditionalFormatAverageRule::TwoStandardDeviationsAbove,
+                (Some(2), false) => ConditionalFormatAverageRule::TwoStandardDeviationsBelow,
+                (Some(3), true) => ConditionalFormatAverageRule::ThreeStandardDeviationsAbove,
+                (Some(3), false) => ConditionalFormatAverageRule::ThreeStandardDeviationsBelow,
+                (Some(_), _) => return Err("的标准差条件无法换算".to_string()),
+                (None, true) if entry.equal_average => {
+                    ConditionalFormatAverageRule::EqualOrAboveAverage
+                }
+                (None, false) if entry.equal_average => {
+                    ConditionalFormatAverageRule::EqualOrBelowAverage
+                }
+                (None, true) => ConditionalFormatAverageRule::AboveAverage,
+                (None, false) => ConditionalFormatAverageRule::BelowAverage,
+            };
+            Rule::Average(
+                ConditionalFormatAverage::new()
+                    .set_rule(rule)
+                    .set_format(format),
+            )
+        }
+        "timePeriod" => {
+            let rule = match entry.time_period.as_deref().unwrap_or("") {
+                "yesterday" => ConditionalFormatDateRule::Yesterday,
+                "today" => ConditionalFormatDateRule::Today,
+                "tomorrow" => ConditionalFormatDateRule::Tomorrow,
+                "last7Days" => ConditionalFormatDateRule::Last7Days,
+                "lastWeek" => ConditionalFormatDateRule::LastWeek,
+                "thisWeek" => ConditionalFormatDateRule::ThisWeek,
+                "nextWeek" => ConditionalFormatDateRule::NextWeek,
+                "lastMonth" => ConditionalFormatDateRule::LastMonth,
+                "thisMonth" => ConditionalFormatDateRule::ThisMonth,
+                "nextMonth" => ConditionalFormatDateRule::NextMonth,
+                other => return Err(format!("的日期条件“{other}”无法换算")),
+            };
+            Rule::Date(
+                ConditionalFormatDate::new()
+                    .set_rule(rule)
+                    .set_format(format),
+            )
+        }
+        "colorScale" => {
+            let points = scale_points(entry).ok_or("的色阶设置不完整")?;
+            match points.as_slice() {
+                [(min_type, min, min_color), (max_type, max, max_color)] => Rule::TwoColorScale(
+                    ConditionalFormat2ColorScale::new()
+                        .set_minimum(*min_type, min.clone())
+                        .set_maximum(*max_type, max.clone())
+                        .set_minimum_color(*min_color)
+                        .set_maximum_color(*max_color),
+                ),
+                [
+                    (min_type, min, min_color),
+                    (mid_type, mid, mid_color),
+                    (max_type, max, max_color),
+                ] => Rule::ThreeColorScale(
+                    ConditionalFormat3ColorScale::new()
+                        .set_minimum(*min_type, min.clone())
+                        .set_midpoint(*mid_type, mid.clone())
+                        .set_maximum(*max_type, max.clone())
+                        .set_minimum_color(*min_color)
+                        .set_midpoint_color(*mid_color)
+                        .set_maximum_color(*max_color),
+                ),
+                _ => return Err("的色阶设置不完整".to_string()),
+            }
+        }
+        "dataBar" => {
+            let [min, max] = entry.thresholds.as_slice() else {
+                return Err("的数据条设置不完整".to_string());
+            };
+            let (min_type, max_type) = threshold_type(&min.kind)
+                .zip(threshold_type(&max.kind))
+                .ok_or("的数据条设置无法换算")?;
+            let mut bar = ConditionalFormatDataBar::new()
+                .set_minimum(min_type, threshold_value(min))
+                .set_maximum(max_type, threshold_value(max))
+                .set_bar_only(!entry.show_value);
+            if let Some(Some(color)) = entry.colors.first() {
+                bar = bar.set_fill_color(Color::RGB(*color));
+            }
+            Rule::DataBar(bar)
+        }
+        "iconSet" => {
+            let name = entry.icon_set.as_deref().unwrap_or("3TrafficLights1");
+            let icon_type = icon_type(name).ok_or_else(|| format!("的图标集“{name}”无法换算"))?;
+            let icons = entry
+                .thresholds
+                .iter()
+                .map(|threshold| {
+                    let kind = threshold_type(&threshold.kind)?;
+                    Some(
+                        ConditionalFormatCustomIcon::new()
+                            .set_rule(kind, threshold_value(threshold))
+                            .set_greater_than(threshold.greater_than),
+                    )
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or("的图标集设置无法换算")?;
+            let mut set = ConditionalFormatIconSet::new()
+                .set_icon_type(icon_type)
+                .reverse_icons(entry.reverse)
+                .show_icons_only(!entry.show_value);
+            if !icons.is_empty() {
+                set = set.set_icons(&icons);
+            }
+            Rule::IconSet(set)
+        }
+        other => return Err(format!("的类型“{other}”无法换算")),
+    })
+}
+
+/// Type, value and color of each point of a color scale.
+fn scale_points(
+    entry: &RuleEntry,
+) -> Option<Vec<(ConditionalFormatType, ConditionalFormatValue, Color)>> {
+    if entry.thresholds.len() != entry.colors.len() {
+        return None;
+    }
+    entry
+        .thresholds
+        .iter()
+        .zip(&entry.colors)
+        .map(|(threshold, color)| {
+            Some((
+                threshold_type(&threshold.kind)?,
+                threshold_value(threshold),
+                Color::RGB((*color)?),
+            ))
+        })
+        .collect()
+}
+
+fn threshold_type(kind: &str) -> Option<ConditionalFormatType> {
+    Some(match kind {
+        "min" => ConditionalFormatType::Lowest,
+        "max" => ConditionalFormatType::Highest,
+        "num" => ConditionalFormatType::Number,
+        "percent" => ConditionalFormatType::Percent,
+        "percentile" => ConditionalFormatType::Percentile,
+        "formula" => ConditionalFormatType::Formula,
+        "autoMin" | "autoMax" => ConditionalFormatType::Automatic,
+        _ => return None,
+    })
+}
+
+/// A threshold's value: a number when it is one, otherwise a formula.
+fn threshold_value(threshold: &Threshold) -> ConditionalFormatValue {
+    let value = threshold.value.as_deref().unwrap_or("0").trim();
+    match value.parse::<f64>() {
+        Ok(number) => number.into(),
+        Err(_) => Formula::new(value).into(),
+    }
+}
+
+/// The icon set named by an `iconSet` attribute.
+fn icon_type(name: &str) -> Option<ConditionalFormatIconType> {
+    Some(match name {
+        "3Arrows" => ConditionalFormatIconType::ThreeArrows,
+        "3ArrowsGray" => ConditionalFormatIconType::ThreeArrowsGray,
+        "3Flags" => ConditionalFormatIconType::ThreeFlags,
+        "3TrafficLights1" => ConditionalFormatIconType::ThreeTrafficLights,
+        "3TrafficLights2" => ConditionalFormatIconType::ThreeTrafficLightsWithRim,
+        "3Signs" => ConditionalFormatIconType::ThreeSigns,
+        "3Symbols" => ConditionalFormatIconType::ThreeSymbolsCircled,
+        "3Symbols2" => ConditionalFormatIconType::ThreeSymbols,
+        "3Stars" => ConditionalFormatIconType::ThreeStars,
+        "3Triangles" => ConditionalFormatIconType::ThreeTriangles,
+        "4Arrows" => ConditionalFormatIconType::FourArrows,
+        "4ArrowsGray" => ConditionalFormatIconType::FourArrowsGray,
+        "4RedToBlack" => ConditionalFormatIconType::FourRedToBlack,
+        "4Rating" => ConditionalFormatIconType::FourHistograms,
+        "4TrafficLights" => ConditionalFormatIconType::FourTrafficLights,
+        "5Arrows" => ConditionalFormatIconType::FiveArrows,
+        "5ArrowsGray" => ConditionalFormatIconType::FiveArrowsGray,
+        "5Rating" => ConditionalFormatIconType::FiveHistograms,
+        "5Quarters" => ConditionalFormatIconType::FiveQuadrants,
+        "5Boxes" => ConditionalFormatIconType::FiveBoxes,
+        _ => return None,
+    })
+}
+
+/// The part of a differential style being read.
+#[derive(Clone, Copy, PartialEq)]
+enum DxfPart {
+    Other,
+    Font,
+    Fill,
+    /// Inside `<border>`, and inside one of its sides when known.
+    Border(Option<BorderSide>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BorderSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// The differential styles (`<dxfs>`) of styles.xml as formats: font style and color, number
+/// format, fill color and borders.
+fn parse_dxfs(styles_xml: &str, palette: &[u32]) -> Vec<Format> {
+    let mut reader = XmlReader::from_str(styles_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut dxfs = Vec::new();
+    let mut current: Option<Format> = None;
+    let mut part = DxfPart::Other;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        let (e, start) = match event {
+            Event::Eof => break,
+            Event::Start(e) => (e, true),
+            Event::Empty(e) => (e, false),
+            Event::End(e) => {
+                match e.local_name().as_ref() {
+                    b"dxf" => dxfs.extend(current.take()),
+                    b"font" | b"fill" | b"border" => part = DxfPart::Other,
+                    b"left" | b"right" | b"top" | b"bottom" => part = DxfPart::Border(None),
+                    _ => {}
+                }
+                buf.clear();
+                continue;
+            }
+            _ => {
+                buf.clear();
+                continue;
+            }
+        };
+        let name = e.local_name();
+        if name.as_ref() == b"dxf" {
+            if start {
+                current = Some(Format::new());
+            } else {
+                dxfs.push(Format::new());
+            }
+        }
+        let Some(format) = current.take() else {
+            buf.clear();
+            continue;
+        };
+        let enabled = attribute(&e, &reader, b"val").is_none_or(|v| v != "0" && v != "false");
+        let color = || read_color(&e, &reader, palette).map(Color::RGB);
+        current = Some(match (part, name.as_ref()) {
+            (_, b"font") if start => {
+                part = DxfPart::Font;
+                format
+            }
+            (_, b"fill") if start => {
+                part = DxfPart::Fill;
+                format
+            }
+            (_, b"border") if start => {
+                part = DxfPart::Border(None);
+                format
+            }
+            (DxfPart::Font, b"b") if enabled => format.set_bold(),
+            (DxfPart::Font, b"i") if enabled => format.set_italic(),
+            (DxfPart::Font, b"strike") if enabled => format.set_font_strikethrough(),
+            (DxfPart::Font, b"u") => match attribute(&e, &reader, b"val").as_deref() {
+                Some("none") => format,
+                Some("double") => format.set_underline(FormatUnderline::Double),
+                _ => format.set_underline(FormatUnderline::Single),
+            },
+            (DxfPart::Font, b"color") => match color() {
+                Some(color) => format.set_font_color(color),
+                None => format,
+            },
+            (_, b"numFmt") => match attribute(&e, &reader, b"formatCode") {
+                Some(code) => format.set_num_format(code),
+                None => format,
+            },
+            (DxfPart::Fill, b"bgColor") => match color() {
+                Some(color) => format.set_background_color(color),
+                None => format,
+            },
+            (DxfPart::Fill, b"fgColor") => match color() {
+                Some(color) => format.set_foreground_color(color),
+                None => format,
+            },
+            (DxfPart::Border(_), side @ (b"left" | b"right" | b"top" | b"bottom")) => {
+                let side = match side {
+                    b"left" => BorderSide::Left,
+                    b"right" => BorderSide::Right,
+                    b"top" => BorderSide::Top,
+                    _ => BorderSide::Bottom,
+                };
+                if start {
+                    part = DxfPart::Border(Some(side));
+                }
+                match attribute(&e, &reader, b"style").and_then(|style| border_style(&style)) {
+                    Some(style) => match side {
+                        BorderSide::Left => format.set_border_left(style),
+                        BorderSide::Right => format.set_border_right(style),
+                        BorderSide::Top => format.set_border_top(style),
+                        BorderSide::Bottom => format.set_border_bottom(style),
+                    },
+                    None => format,
+                }
+            }
+            (DxfPart::Border(Some(side)), b"color") => match (color(), side) {
+                (Some(color), BorderSide::Left) => format.set_border_left_color(color),
+                (Some(color), BorderSide::Right) => format.set_border_right_color(color),
+                (Some(color), BorderSide::Top) => format.set_border_top_color(color),
+                (Some(color), BorderSide::Bottom) => format.set_border_bottom_color(color),
+                (None, _) => format,
+            },
+            _ => format,
+        });
+        buf.clear();
+    }
+    dxfs
+}
+
+fn border_style(style: &str) -> Option<FormatBorder> {
+    Some(match style {
+        "thin" => FormatBorder::Thin,
+        "medium" => FormatBorder::Medium,
+        "dashed" => FormatBorder::Dashed,
+        "dotted" => FormatBorder::Dotted,
+        "thick" => FormatBorder::Thick,
+        "double" => FormatBorder::Double,
+        "hair" => FormatBorder::Hair,
+        "mediumDashed" => FormatBorder::MediumDashed,
+        "dashDot" => FormatBorder::DashDot,
+        "mediumDashDot" => FormatBorder::MediumDashDot,
+        "dashDotDot" => FormatBorder::DashDotDot,
+        "mediumDashDotDot" => FormatBorder::MediumDashDotDot,
+        "slantDashDot" => FormatBorder::SlantDashDot,
+        _ => return None,
+    })
+}
+
+/// The RGB value of a `<color>`-like element: `rgb`, or a `theme` color of `palette` lightened
+/// or darkened by `tint`. Indexed and automatic colors give `None`.
+fn read_color(e: &BytesStart, reader: &XmlReader<&[u8]>, palette: &[u32]) -> Option<u32> {
+    if let Some(rgb) = attribute(e, reader, b"rgb") {
+        // ARGB; the alpha byte is ignored as Excel does.
+        let rgb = &rgb[rgb.len().saturating_sub(6)..];
+        return u32::from_str_radix(rgb, 16).ok();
+    }
+    let theme: usize = attribute(e, reader, b"theme")?.parse().ok()?;
+    let color = *palette.get(theme)?;
+    let tint = attribute(e, reader, b"tint")
+        .and_then(|tint| tint.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    Some(apply_tint(color, tint))
+}
+
+/// Excel's tint: moves the color's lightness towards black (negative) or white (positive).
+fn apply_tint(color: u32, tint: f64) -> u32 {
+    if tint == 0.0 {
+        return color;
+    }
+    let channel = |shift: u32| f64::from((color >> shift) & 0xFF) / 255.0;
+    let (red, green, blue) = (channel(16), channel(8), channel(0));
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let mut lightness = (max + min) / 2.0;
+    let delta = max - min;
+    let saturation = if delta == 0.0 {
+        0.0
+    } else {
+        delta / (1.0 - (2.0 * lightness - 1.0).abs())
+    };
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    };
+
+    lightness = if tint < 0.0 {
+        lightness * (1.0 + tint)
+    } else {
+        lightness * (1.0 - tint) + tint
+    };
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = lightness - chroma / 2.0;
+    let (red, green, blue) = match hue {
+        h if h < 60.0 => (chroma, x, 0.0),
+        h if h < 120.0 => (x, chroma, 0.0),
+        h if h < 180.0 => (0.0, chroma, x),
+        h if h < 240.0 => (0.0, x, chroma),
+        h if h < 300.0 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let byte = |value: f64| ((value + m) * 255.0).round().clamp(0.0, 255.0) as u32;
+    (byte(red) << 16) | (byte(green) << 8) | byte(blue)
+}
+
+/// The theme colors in the order `theme` attributes count them: light 1, dark 1, light 2,
+/// dark 2, the six accents and the two hyperlink colors.
+fn parse_theme_palette(theme_xml: &str) -> Vec<u32> {
+    let mut reader = XmlReader::from_str(theme_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut colors = Vec::new();
+    let mut in_scheme = false;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) if e.local_name().as_ref() == b"clrScheme" => in_scheme = true,
+            Event::End(e) if e.local_name().as_ref() == b"clrScheme" => break,
+            Event::Start(e) | Event::Empty(e) if in_scheme => {
+                let value = match e.local_name().as_ref() {
+                    b"srgbClr" => attribute(&e, &reader, b"val"),
+                    b"sysClr" => attribute(&e, &reader, b"lastClr"),
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    colors.push(u32::from_str_radix(&value, 16).unwrap_or(0));
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    // The scheme lists dark 1 before light 1 and dark 2 before light 2.
+    if colors.len() >= 4 {
+        colors.swap(0, 1);
+        colors.swap(2, 3);
+    }
+    colors
+}
+
+fn attribute(e: &BytesStart, reader: &XmlReader<&[u8]>, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref() == name)
+        .and_then(|attr| attr.decode_and_unescape_value(reader).ok())
+        .map(|value| value.into_owned())
+}
@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::columns::resolve_column_list;
+use super::{ChunkExtras, sheet_row_number, write_chunk};
+
+/// Drops repeated data rows before splitting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dedup {
+    /// Columns that identify a row, as in `A, C-E` or `姓名, 手机号`; empty compares whole rows.
+    pub key_columns: String,
+    /// Keep the last occurrence of each row instead of the first.
+    pub keep_last: bool,
+    /// Ignore leading and trailing whitespace.
+    pub trim: bool,
+    /// Ignore upper and lower case.
+    pub ignore_case: bool,
+    /// Treat full-width letters, digits, punctuation and spaces like their half-width forms.
+    pub fold_width: bool,
+}
+
+/// A removed row (index into the data rows) and the row that was kept in its place.
+pub struct Duplicate {
+    pub removed: usize,
+    pub kept: usize,
+}
+
+/// Removes duplicates from `order` (indices into `rows`), keeping each row's first or last
+/// occurrence where it was, and returns the removed rows in sheet order.
+pub fn remove_duplicates(
+    order: &mut Vec<usize>,
+    dedup: &Dedup,
+    header: &[Vec<String>],
+    first_column: usize,
+    rows: &[Vec<String>],
+) -> Result<Vec<Duplicate>> {
+    let key_columns = if dedup.key_columns.trim().is_empty() {
+        None
+    } else {
+        Some(resolve_column_list(
+            &dedup.key_columns,
+            header,
+            first_column,
+        )?)
+    };
+
+    let key_of = |row: &[String]| -> Vec<String> {
+        match &key_columns {
+            Some(columns) => columns
+                .iter()
+                .map(|&col| normalize(row.get(col).map(String::as_str).unwrap_or(""), dedup))
+                .collect(),
+            None => {
+                // Trailing empty cells do not make rows different.
+                let width = row
+                    .iter()
+                    .rposition(|value| !normalize(value, dedup).is_empty())
+                    .map_or(0, |last| last + 1);
+                row[..width]
+                    .iter()
+                    .map(|value| normalize(value, dedup))
+                    .collect()
+            }
+        }
+    };
+
+    let mut occurrences: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for &idx in order.iter() {
+        occurrences.entry(key_of(&rows[idx])).or_default().push(idx);
+    }
+
+    let mut duplicates = Vec::new();
+    for indices in occurrences.values().filter(|indices| indices.len() > 1) {
+        let kept = if dedup.keep_last {
+            indices[indices.len() - 1]
+        } else {
+            indices[0]
+        };
+        duplicates.extend(
+            indices
+                .iter()
+                .filter(|&&idx| idx != kept)
+                .map(|&removed| Duplicate { removed, kept }),
+        );
+    }
+    duplicates.sort_unstable_by_key(|duplicate| duplicate.removed);
+
+    let removed: HashSet<usize> = duplicates
+        .iter()
+        .map(|duplicate| duplicate.removed)
+        .collect();
+    order.retain(|idx| !removed.contains(idx));
+    Ok(duplicates)
+}
+
+fn normalize(value: &str, dedup: &Dedup) -> String {
+    let value = if dedup.trim { value.trim() } else { value };
+    let value: String = if dedup.fold_width {
+        value
+            .chars()
+            .map(|ch| match ch {
+                '\u{3000}' => ' ',
+                '\u{FF01}'..='\u{FF5E}' => char::from_u32(ch as u32 - 0xFEE0).unwrap_or(ch),
+                _ => ch,
+            })
+            .collect()
+    } else {
+        value.to_string()
+    };
+    if dedup.ignore_case {
+        value.to_lowercase()
+    } else {
+        value
+    }
+}
+
+/// `<stem>_duplicates.xlsx` next to the source.
+fn build_report_path(source: &Path) -> PathBuf {
+    let parent = source
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("split");
+    parent.join(format!("{stem}_duplicates.xlsx"))
+}
+
+/// Writes the removed rows with the header, each preceded by its own sheet row number and the
+/// number of the row that was kept instead. The table's first row is sheet row `origin_row`
+/// (0-based).
+pub fn write_report(
+    source: &Path,
+    header: &[Vec<String>],
+    origin_row: usize,
+    rows: &[Vec<String>],
+    duplicates: &[Duplicate],
+) -> Result<PathBuf> {
+    let header_rows = header.len();
+    let last_header = header_rows.saturating_sub(1);
+    let report_header: Vec<Vec<String>> = header
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let labels = if idx == last_header {
+                ["源行号", "保留的行号"].map(String::from)
+            } else {
+                [String::new(), String::new()]
+            };
+            labels.into_iter().chain(row.iter().cloned()).collect()
+        })
+        .collect();
+    let report_rows: Vec<Vec<String>> = duplicates
+        .iter()
+        .map(|duplicate| {
+            [duplicate.removed, duplicate.kept]
+                .map(|idx| sheet_row_number(origin_row, header_rows + idx).to_string())
+                .into_iter()
+                .chain(rows[duplicate.removed].iter().cloned())
+                .collect()
+        })
+        .collect();
+    let report_refs: Vec<&[String]> = report_rows.iter().map(Vec::as_slice).collect();
+
+    let path = build_report_path(source);
+    write_chunk(
+        &path,
+        &report_header,
+        &report_refs,
+        None,
+        None,
+        &ChunkExtras::default(),
+    )?;
+    Ok(path)
+}
@@ -0,0 +1,394 @@
+use anyhow::{Result, anyhow};
+use calamine::Data;
+use chrono::NaiveDate;
+use regex::Regex;
+
+use super::period::{cell_date, parse_text_date};
+use super::resolve_column;
+
+/// A compiled row filter such as `状态 = 已完成 AND 金额 > 1000`.
+///
+/// Conditions are `列 运算符 值` with `= != <> > >= < <=`, `列 CONTAINS 值`, `列 MATCHES 正则`
+/// and `列 IS [NOT] EMPTY`, combined with `AND`/`OR`/`NOT` and parentheses. The Chinese
+/// keywords `且 或 非 包含 匹配 为空 不为空` work as well; keywords must be separated from
+/// their neighbours by spaces. Columns are header texts or letters (`[订单 编号]` for names
+/// with spaces); values may be quoted. Numbers and dates are compared by value when both
+/// sides are numbers or dates, everything else as text.
+pub struct Filter {
+    expr: Expr,
+}
+
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        column: usize,
+        op: CompareOp,
+        value: Literal,
+    },
+    Contains {
+        column: usize,
+        needle: String,
+    },
+    Matches {
+        column: usize,
+        regex: Regex,
+    },
+    Empty {
+        column: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+struct Literal {
+    text: String,
+    number: Option<f64>,
+    date: Option<NaiveDate>,
+}
+
+impl Filter {
+    /// Compiles `text`, resolving column names against `header`, whose first cell sits in sheet
+    /// column `first_column`. Errors name the character position and the token where parsing
+    /// failed.
+    pub fn parse(text: &str, header: &[Vec<String>], first_column: usize) -> Result<Filter> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            end: text.chars().count() + 1,
+            header,
+            first_column,
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.error_at(token, "条件之后有多余的内容"));
+        }
+        Ok(Filter { expr })
+    }
+
+    pub fn matches(&self, cells: &[Data], texts: &[String]) -> bool {
+        self.expr.eval(cells, texts)
+    }
+}
+
+impl Expr {
+    fn eval(&self, cells: &[Data], texts: &[String]) -> bool {
+        let text = |column: &usize| texts.get(*column).map(|v| v.trim()).unwrap_or("");
+        match self {
+            Expr::And(left, right) => left.eval(cells, texts) && right.eval(cells, texts),
+            Expr::Or(left, right) => left.eval(cells, texts) || right.eval(cells, texts),
+            Expr::Not(inner) => !inner.eval(cells, texts),
+            Expr::Compare { column, op, value } => {
+                let cell = cells.get(*column).unwrap_or(&Data::Empty);
+                let ordering = match (cell_number(cell), value.number, cell_date(cell), value.date)
+                {
+                    (Some(a), Some(b), _, _) => a.partial_cmp(&b),
+                    (_, _, Some(a), Some(b)) => Some(a.cmp(&b)),
+                    _ => Some(text(column).cmp(value.text.as_str())),
+                };
+                let Some(ordering) = ordering else {
+                    return false;
+                };
+                match op {
+                    CompareOp::Eq => ordering.is_eq(),
+                    CompareOp::Ne => ordering.is_ne(),
+                    CompareOp::Gt => ordering.is_gt(),
+                    CompareOp::Ge => ordering.is_ge(),
+                    CompareOp::Lt => ordering.is_lt(),
+                    CompareOp::Le => ordering.is_le(),
+                }
+            }
+            Expr::Contains { column, needle } => text(column).contains(needle.as_str()),
+            Expr::Matches { column, regex } => regex.is_match(text(column)),
+            Expr::Empty { column } => text(column).is_empty(),
+        }
+    }
+}
+
+fn cell_number(cell: &Data) -> Option<f64> {
+    match cell {
+        Data::Float(f) => Some(*f),
+        Data::Int(i) => Some(*i as f64),
+        Data::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    Contains,
+    Matches,
+    Is,
+    Empty,
+    IsEmpty,
+    NotEmpty,
+    /// A column or value: a bare word, a quoted string or a `[bracketed name]`.
+    Word(String),
+}
+
+struct Token {
+    kind: TokenKind,
+    /// 1-based character position in the expression.
+    pos: usize,
+    text: String,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let ch = chars[idx];
+        let start = idx;
+        if ch.is_whitespace() {
+            idx += 1;
+            continue;
+        }
+
+        let two: String = chars[idx..(idx + 2).min(chars.len())].iter().collect();
+        let symbol = match two.as_str() {
+            ">=" => Some((TokenKind::Op(CompareOp::Ge), 2)),
+            "<=" => Some((TokenKind::Op(CompareOp::Le), 2)),
+            "!=" | "<>" => Some((TokenKind::Op(CompareOp::Ne), 2)),
+            "==" => Some((TokenKind::Op(CompareOp::Eq), 2)),
+            "&&" => Some((TokenKind::And, 2)),
+            "||" => Some((TokenKind::Or, 2)),
+            _ => match ch {
+                '(' | '（' => Some((TokenKind::LParen, 1)),
+                ')' | '）' => Some((TokenKind::RParen, 1)),
+                '=' => Some((TokenKind::Op(CompareOp::Eq), 1)),
+                '>' => Some((TokenKind::Op(CompareOp::Gt), 1)),
+                '<' => Some((TokenKind::Op(CompareOp::Lt), 1)),
+                '!' => Some((TokenKind::Not, 1)),
+                _ => None,
+            },
+        };
+        if let Some((kind, len)) = symbol {
+            idx += len;
+            tokens.push(Token {
+                kind,
+                pos: start + 1,
+                text: chars[start..idx].iter().collect(),
+            });
+            continue;
+        }
+
+        let closing = match ch {
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            '“' => Some('”'),
+            '‘' => Some('’'),
+            '[' => Some(']'),
+            '`' => Some('`'),
+            _ => None,
+        };
+        if let Some(closing) = closing {
+            let Some(len) = chars[idx + 1..].iter().position(|&c| c == closing) else {
+                return Err(anyhow!(
+                    "筛选条件第 {} 个字符附近“{ch}”有误：缺少与之配对的 {closing}",
+                    start + 1
+                ));
+            };
+            idx += len + 2;
+            tokens.push(Token {
+                kind: TokenKind::Word(chars[start + 1..idx - 1].iter().collect()),
+                pos: start + 1,
+                text: chars[start..idx].iter().collect(),
+            });
+            continue;
+        }
+
+        while idx < chars.len()
+            && !chars[idx].is_whitespace()
+            && !"()（）=!<>&|\"'“‘[`".contains(chars[idx])
+        {
+            idx += 1;
+        }
+        let word: String = chars[start..idx].iter().collect();
+        let kind = match word.to_lowercase().as_str() {
+            "and" | "且" | "并且" => TokenKind::And,
+            "or" | "或" | "或者" => TokenKind::Or,
+            "not" | "非" => TokenKind::Not,
+            "contains" | "包含" => TokenKind::Contains,
+            "matches" | "regex" | "匹配" => TokenKind::Matches,
+            "is" => TokenKind::Is,
+            "empty" => TokenKind::Empty,
+            "为空" => TokenKind::IsEmpty,
+            "不为空" | "非空" => TokenKind::NotEmpty,
+            _ => TokenKind::Word(word.clone()),
+        };
+        tokens.push(Token {
+            kind,
+            pos: start + 1,
+            text: word,
+        });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    /// Position reported when the expression ends too early.
+    end: usize,
+    header: &'a [Vec<String>],
+    first_column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<&'a Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| anyhow!("筛选条件第 {} 个字符处条件不完整", self.end))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn error_at(&self, token: &Token, reason: &str) -> anyhow::Error {
+        anyhow!(
+            "筛选条件第 {} 个字符附近“{}”有误：{reason}",
+            token.pos,
+            token.text
+        )
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.peek().is_some_and(|token| token.kind == TokenKind::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut left = self.parse_not()?;
+        while self
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::And)
+        {
+            self.pos += 1;
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::Not)
+        {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let token = self.next()?;
+        match &token.kind {
+            TokenKind::LParen => {
+                let expr = self.parse_or()?;
+                let Some(closing) = self.peek() else {
+                    return Err(self.error_at(token, "缺少与之配对的右括号"));
+                };
+                self.pos += 1;
+                if closing.kind != TokenKind::RParen {
+                    return Err(self.error_at(closing, "此处应为右括号"));
+                }
+                Ok(expr)
+            }
+            TokenKind::Word(name) => {
+                let column = resolve_column(name, self.header, self.first_column)
+                    .map_err(|_| self.error_at(token, "表头中没有这一列"))?;
+                self.parse_condition(column)
+            }
+            _ => Err(self.error_at(token, "此处应为列名或左括号")),
+        }
+    }
+
+    fn parse_condition(&mut self, column: usize) -> Result<Expr> {
+        let token = self.next()?;
+        match &token.kind {
+            TokenKind::Op(op) => {
+                let text = self.parse_value()?.to_string();
+                Ok(Expr::Compare {
+                    column,
+                    op: *op,
+                    value: Literal {
+                        number: text.trim().parse().ok(),
+                        date: parse_text_date(&text),
+                        text,
+                    },
+                })
+            }
+            TokenKind::Contains => Ok(Expr::Contains {
+                column,
+                needle: self.parse_value()?.to_string(),
+            }),
+            TokenKind::Matches => {
+                let value_token = self.peek();
+                let pattern = self.parse_value()?;
+                let regex = Regex::new(pattern).map_err(|err| match value_token {
+                    Some(value_token) => {
+                        self.error_at(value_token, &format!("正则表达式无效：{err}"))
+                    }
+                    None => anyhow!("正则表达式无效：{err}"),
+                })?;
+                Ok(Expr::Matches { column, regex })
+            }
+            TokenKind::IsEmpty => Ok(Expr::Empty { column }),
+            TokenKind::NotEmpty => Ok(Expr::Not(Box::new(Expr::Empty { column }))),
+            TokenKind::Is => {
+                let mut next = self.next()?;
+                let negated = next.kind == TokenKind::Not;
+                if negated {
+                    next = self.next()?;
+                }
+                if next.kind != TokenKind::Empty {
+                    return Err(self.error_at(next, "IS 之后应为 EMPTY 或 NOT EMPTY"));
+                }
+                let expr = Expr::Empty { column };
+                Ok(if negated {
+                    Expr::Not(Box::new(expr))
+                } else {
+                    expr
+                })
+            }
+            _ => Err(self.error_at(token, "此处应为比较运算符、CONTAINS、MATCHES 或 IS EMPTY")),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<&'a str> {
+        let token = self.next()?;
+        match &token.kind {
+            TokenKind::Word(value) => Ok(value),
+            _ => Err(self.error_at(token, "此处应为比较的值")),
+        }
+    }
+}
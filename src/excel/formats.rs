@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use calamine::{Data, ExcelDateTime, ExcelDateTimeType};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use quick_xml::{Reader as XmlReader, events::Event};
+
+use super::{open_xlsx_archive, parse_cell_ref, read_sheet_xml, read_zip_entry};
+
+/// Days between the 1904 and 1900 date systems.
+pub const DATE1904_OFFSET: f64 = 1462.0;
+
+/// Shown for dates whose number format is unknown (e.g. `.xls` files).
+const DATE_TIME_FALLBACK: &str = "yyyy-mm-dd hh:mm:ss";
+
+/// Shown for durations whose number format is unknown.
+const DURATION_FALLBACK: &str = "[h]:mm:ss";
+
+/// The number format of every cell of one sheet, from `xl/styles.xml`.
+#[derive(Default)]
+pub struct NumberFormats {
+    /// Format code of each cell style (`cellXfs` entry); `None` for General.
+    styles: Vec<Option<String>>,
+    /// Style of the cells that have a number format, by row and column of the read range.
+    cells: HashMap<(usize, usize), usize>,
+}
+
+impl NumberFormats {
+    /// Reads the number formats of `sheet_name`. `origin` is the sheet row and column of the
+    /// first cell calamine returned. Only `.xlsx` files are read; other files have no formats.
+    pub fn load(source: &Path, sheet_name: &str, origin: (usize, usize)) -> Result<Self> {
+        let Some(mut archive) = open_xlsx_archive(source)? else {
+            return Ok(Self::default());
+        };
+        // styles.xml is optional; without it every cell is General.
+        let Ok(styles_xml) = read_zip_entry(&mut archive, "xl/styles.xml") else {
+            return Ok(Self::default());
+        };
+        let styles = parse_styles(&styles_xml);
+        if styles.iter().all(Option::is_none) {
+            return Ok(Self::default());
+        }
+        let (_, sheet_xml) = read_sheet_xml(&mut archive, sheet_name)?;
+        let cells = parse_cell_styles(&sheet_xml, &styles, origin);
+        Ok(Self { styles, cells })
+    }
+
+    /// Format code of the cell at `row`, `col` of the read range, `None` for General.
+    pub fn code(&self, row: usize, col: usize) -> Option<&str> {
+        let style = *self.cells.get(&(row, col))?;
+        self.styles.get(style)?.as_deref()
+    }
+}
+
+/// Moves the dates of workbooks using the 1904 date system (old Mac Excel) to the 1900 system
+/// the outputs are written in, so serial numbers compare and write correctly. calamine already
+/// applies the workbook's `date1904` flag when converting to a date, which tells them apart.
+pub fn rebase_1904(cells: &mut [Vec<Data>]) {
+    for cell in cells.iter_mut().flatten() {
+        let Data::DateTime(date_time) = cell else {
+            continue;
+        };
+        if date_time.is_duration() {
+            continue;
+        }
+        let as_1900 = ExcelDateTime::new(date_time.as_f64(), ExcelDateTimeType::DateTime, false);
+        if as_1900.as_datetime() != date_time.as_datetime() {
+            *date_time = ExcelDateTime::new(
+                date_time.as_f64() + DATE1904_OFFSET,
+                ExcelDateTimeType::DateTime,
+                false,
+            );
+        }
+    }
+}
+
+/// The format code a date or duration is shown and written with: `code` when it is a date or
+/// time format, otherwise a full date and time, or `[h]:mm:ss` for durations.
+pub fn date_time_code<'a>(date_time: &ExcelDateTime, code: Option<&'a str>) -> &'a str {
+    match code {
+        Some(code) if tokenize(code).iter().any(Part::is_date_time) => code,
+        _ if date_time.is_duration() => DURATION_FALLBACK,
+        _ => DATE_TIME_FALLBACK,
+    }
+}
+
+/// Renders a date or duration the way Excel shows it with `code` (see [`date_time_code`]).
+pub fn render_date_time(date_time: &ExcelDateTime, code: Option<&str>) -> String {
+    let parts = resolve_minutes(tokenize(date_time_code(date_time, code)));
+    let serial = date_time.as_f64();
+    let has_date = parts.iter().any(|part| {
+        matches!(
+            part,
+            Part::Year(_) | Part::Month(_) | Part::Day(_) | Part::Weekday(_)
+        )
+    });
+    // Negative values only make sense as durations.
+    let negative = serial < 0.0 && !has_date;
+    let serial = if negative { -serial } else { serial };
+
+    let digits = parts
+        .iter()
+        .find_map(|part| match part {
+            Part::Fraction(digits) => Some((*digits).min(3)),
+            _ => None,
+        })
+        .unwrap_or(0);
+    let unit = 10i64.pow(digits as u32);
+    let ticks = (serial * 86_400.0 * unit as f64).round() as i64;
+    let (total_seconds, fraction) = (ticks.div_euclid(unit), ticks.rem_euclid(unit));
+    let seconds_of_day = total_seconds.rem_euclid(86_400);
+    // Excel counts the nonexistent 1900-02-29, so serials before it are one day off.
+    let days = total_seconds.div_euclid(86_400) + i64::from(serial < 60.0);
+    let date_time = NaiveDate::from_ymd_opt(1899, 12, 30)
+        .and_then(|epoch| epoch.checked_add_signed(Duration::days(days)))
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date + Duration::seconds(seconds_of_day))
+        .unwrap_or(NaiveDateTime::MIN);
+
+    let twelve_hour = parts.iter().any(|part| matches!(part, Part::AmPm(_)));
+    let chinese = is_chinese_locale(code.unwrap_or(""));
+    let mut text = String::from(if negative { "-" } else { "" });
+    for part in &parts {
+        match part {
+            Part::Literal(literal) => text.push_str(literal),
+            Part::Year(width) if *width <= 2 => {
+                text.push_str(&format!("{:02}", date_time.year().rem_euclid(100)))
+            }
+            Part::Year(_) => text.push_str(&date_time.year().to_string()),
+            Part::Month(width) => {
+                text.push_str(&month_text(date_time.month0() as usize, *width, chinese))
+            }
+            Part::Day(width) if *width <= 2 => text.push_str(&pad(date_time.day(), *width)),
+            Part::Day(width) => text.push_str(&weekday_text(&date_time, *width, chinese)),
+            Part::Weekday(width) => {
+                let day = WEEKDAYS_CHINESE[date_time.weekday().num_days_from_monday() as usize];
+                if *width >= 4 {
+                    text.push_str("星期");
+                }
+                text.push_str(day);
+            }
+            Part::Hour(width) => {
+                let hour = date_time.hour();
+                let hour = match (twelve_hour, hour % 12) {
+                    (false, _) => hour,
+                    (true, 0) => 12,
+                    (true, hour) => hour,
+                };
+                text.push_str(&pad(hour, *width));
+            }
+            Part::Minute(width) => text.push_str(&pad((total_seconds / 60 % 60) as u32, *width)),
+            Part::Second(width) => text.push_str(&pad((total_seconds % 60) as u32, *width)),
+            Part::Fraction(_) => {
+                text.push('.');
+                text.push_str(&format!("{fraction:0digits$}"));
+            }
+            Part::Elapsed(unit, width) => {
+                let value = match unit {
+                    'h' => total_seconds / 3600,
+                    'm' => total_seconds / 60,
+                    _ => total_seconds,
+                };
+                text.push_str(&format!("{value:0width$}"));
+            }
+            Part::AmPm(style) => {
+                let morning = date_time.hour() < 12;
+                text.push_str(match (style, morning) {
+                    (AmPm::Chinese, true) => "上午",
+                    (AmPm::Chinese, false) => "下午",
+                    (AmPm::Long, true) if chinese => "上午",
+                    (AmPm::Long, false) if chinese => "下午",
+                    (AmPm::Long, true) => "AM",
+                    (AmPm::Long, false) => "PM",
+                    (AmPm::Short, true) => "A",
+                    (AmPm::Short, false) => "P",
+                });
+            }
+            Part::MinuteOrMonth(_) => {}
+        }
+    }
+    text
+}
+
+/// Format codes of the built-in number formats, as shown by Excel in Chinese (PRC) locale.
+fn builtin_format(id: u32) -> Option<&'static str> {
+    Some(match id {
+        1 => "0",
+        2 => "0.00",
+        3 => "#,##0",
+        4 => "#,##0.00",
+        9 => "0%",
+        10 => "0.00%",
+        11 => "0.00E+00",
+        12 => "# ?/?",
+        13 => "# ??/??",
+        14 => "yyyy/m/d",
+        15 => "d-mmm-yy",
+        16 => "d-mmm",
+        17 => "mmm-yy",
+        18 => "h:mm AM/PM",
+        19 => "h:mm:ss AM/PM",
+        20 => "h:mm",
+        21 => "h:mm:ss",
+        22 => "yyyy/m/d h:mm",
+        27 | 36 | 50 | 52 | 57 => "yyyy\"年\"m\"月\"",
+        28 | 29 | 51 | 53 | 54 | 58 => "m\"月\"d\"日\"",
+        30 => "m-d-yy",
+        31 => "yyyy\"年\"m\"月\"d\"日\"",
+        32 => "h\"时\"mm\"分\"",
+        33 => "h\"时\"mm\"分\"ss\"秒\"",
+        34 | 55 => "上午/下午h\"时\"mm\"分\"",
+        35 | 56 => "上午/下午h\"时\"mm\"分\"ss\"秒\"",
+        37 => "#,##0 ;(#,##0)",
+        38 => "#,##0 ;[Red](#,##0)",
+        39 => "#,##0.00;(#,##0.00)",
+        40 => "#,##0.00;[Red](#,##0.00)",
+        45 => "mm:ss",
+        46 => "[h]:mm:ss",
+        47 => "mm:ss.0",
+        48 => "##0.0E+0",
+        49 => "@",
+        _ => return None,
+    })
+}
+
+/// Format code of each `cellXfs` entry of styles.xml.
+fn parse_styles(styles_xml: &str) -> Vec<Option<String>> {
+    let mut reader = XmlReader::from_str(styles_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut custom: HashMap<u32, String> = HashMap::new();
+    let mut format_ids: Vec<u32> = Vec::new();
+    let mut in_cell_xfs = false;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) if e.local_name().as_ref() == b"cellXfs" => in_cell_xfs = true,
+            Event::End(e) if e.local_name().as_ref() == b"cellXfs" => in_cell_xfs = false,
+            Event::Start(e) | Event::Empty(e) => {
+                let name = e.local_name();
+                let is_format = name.as_ref() == b"numFmt";
+                if !(is_format || in_cell_xfs && name.as_ref() == b"xf") {
+                    continue;
+                }
+                let mut id = 0;
+                let mut code = None;
+                for attr in e.attributes().flatten() {
+                    let Ok(value) = attr.decode_and_unescape_value(&reader) else {
+                        continue;
+                    };
+                    match attr.key.local_name().as_ref() {
+                        b"numFmtId" => id = value.trim().parse().unwrap_or(0),
+                        b"formatCode" => code = Some(value.into_owned()),
+                        _ => {}
+                    }
+                }
+                if is_format {
+                    custom.insert(id, code.unwrap_or_default());
+                } else {
+                    format_ids.push(id);
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    format_ids
+        .into_iter()
+        .map(|id| {
+            custom
+                .get(&id)
+                .cloned()
+                .or_else(|| builtin_format(id).map(String::from))
+                .filter(|code| !code.is_empty() && !code.eq_ignore_ascii_case("General"))
+        })
+        .collect()
+}
+
+/// Style of every cell in the sheet's `<sheetData>` whose style has a number format, keyed by
+/// position relative to `origin`.
+fn parse_cell_styles(
+    sheet_xml: &str,
+    styles: &[Option<String>],
+    origin: (usize, usize),
+) -> HashMap<(usize, usize), usize> {
+    let mut reader = XmlReader::from_str(sheet_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut cells = HashMap::new();
+    // Rows and cells may omit their reference; they then follow the previous one.
+    let mut row = 0;
+    let mut next_col = 0;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"row" => {
+                    row = e
+                        .try_get_attribute("r")
+                        .ok()
+                        .flatten()
+                        .and_then(|attr| std::str::from_utf8(&attr.value).ok()?.parse().ok())
+                        .map_or(row + 1, |r: usize| r);
+                    next_col = 0;
+                }
+                b"c" => {
+                    let mut position = None;
+                    let mut style = None;
+                    for attr in e.attributes().flatten() {
+                        let Ok(value) = std::str::from_utf8(&attr.value) else {
+                            continue;
+                        };
+                        match attr.key.local_name().as_ref() {
+                            b"r" => position = parse_cell_ref(value),
+                            b"s" => style = value.parse::<usize>().ok(),
+                            _ => {}
+                        }
+                    }
+                    let (cell_row, col) = position.unwrap_or((row.saturating_sub(1), next_col));
+                    next_col = col + 1;
+                    let Some(style) = style else {
+                        continue;
+                    };
+                    if matches!(styles.get(style), Some(Some(_)))
+                        && cell_row >= origin.0
+                        && col >= origin.1
+                    {
+                        cells.insert((cell_row - origin.0, col - origin.1), style);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        buf.clear();
+    }
+    cells
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AmPm {
+    /// `AM/PM`
+    Long,
+    /// `A/P`
+    Short,
+    /// `上午/下午`
+    Chinese,
+}
+
+/// One element of a date or time format code. Widths count the repeated letters.
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Year(usize),
+    /// `m` before it is known whether it means month or minute.
+    MinuteOrMonth(usize),
+    Month(usize),
+    Minute(usize),
+    Day(usize),
+    /// `aaa`/`aaaa`, the Chinese weekday.
+    Weekday(usize),
+    Hour(usize),
+    Second(usize),
+    /// Decimal places of the seconds.
+    Fraction(usize),
+    /// `[h]`, `[mm]`, `[ss]`: elapsed time that does not wrap around.
+    Elapsed(char, usize),
+    AmPm(AmPm),
+}
+
+impl Part {
+    fn is_date_time(&self) -> bool {
+        !matches!(self, Part::Literal(_))
+    }
+}
+
+/// Splits the first section of a format code into its parts.
+fn tokenize(code: &str) -> Vec<Part> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut parts = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        let ch = chars[idx];
+        let rest: String = chars[idx..].iter().take(5).collect();
+        let run = chars[idx..]
+            .iter()
+            .take_while(|other| other.eq_ignore_ascii_case(&ch))
+            .count();
+        match ch.to_ascii_lowercase() {
+            ';' => break,
+            '"' => {
+                let end = chars[idx + 1..]
+                    .iter()
+                    .position(|&other| other == '"')
+                    .map_or(chars.len(), |end| idx + 1 + end);
+                parts.push(Part::Literal(chars[idx + 1..end].iter().collect()));
+                idx = end + 1;
+                continue;
+            }
+            '\\' => {
+                if let Some(next) = chars.get(idx + 1) {
+                    parts.push(Part::Literal(next.to_string()));
+                }
+                idx += 2;
+                continue;
+            }
+            '_' => {
+                parts.push(Part::Literal(" ".into()));
+                idx += 2;
+                continue;
+            }
+            '*' => {
+                idx += 2;
+                continue;
+            }
+            '[' => {
+                let end = chars[idx..]
+                    .iter()
+                    .position(|&other| other == ']')
+                    .map_or(chars.len(), |end| idx + end);
+                let inner: String = chars[idx + 1..end].iter().collect();
+                let inner = inner.to_ascii_lowercase();
+                if let Some(unit) = inner.chars().next()
+                    && matches!(unit, 'h' | 'm' | 's')
+                    && inner.chars().all(|other| other == unit)
+                {
+                    parts.push(Part::Elapsed(unit, inner.len()));
+                }
+                // Anything else is a color, condition or locale tag.
+                idx = end + 1;
+                continue;
+            }
+            _ if rest.eq_ignore_ascii_case("AM/PM") => {
+                parts.push(Part::AmPm(AmPm::Long));
+                idx += 5;
+                continue;
+            }
+            _ if rest == "上午/下午" => {
+                parts.push(Part::AmPm(AmPm::Chinese));
+                idx += 5;
+                continue;
+            }
+            _ if rest
+                .get(..3)
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case("A/P")) =>
+            {
+                parts.push(Part::AmPm(AmPm::Short));
+                idx += 3;
+                continue;
+            }
+            // `E+`/`E-` is the exponent of a scientific format such as `0.00E+00`.
+            'e' if matches!(chars.get(idx + 1), Some('+' | '-')) => {
+                parts.push(Part::Literal(chars[idx..idx + 2].iter().collect()));
+                idx += 2;
+                continue;
+            }
+            _ if chars[idx..]
+                .iter()
+                .take(7)
+                .collect::<String>()
+                .eq_ignore_ascii_case("General") =>
+            {
+                parts.push(Part::Literal("General".into()));
+                idx += 7;
+                continue;
+            }
+            'y' | 'e' => parts.push(Part::Year(if ch.eq_ignore_ascii_case(&'e') {
+                4
+            } else {
+                run
+            })),
+            'm' => parts.push(Part::MinuteOrMonth(run)),
+            'd' => parts.push(Part::Day(run)),
+            'h' => parts.push(Part::Hour(run)),
+            's' => parts.push(Part::Second(run)),
+            'a' if run >= 3 => parts.push(Part::Weekday(run)),
+            '.' if matches!(parts.last(), Some(Part::Second(_) | Part::Elapsed('s', _)))
+                && chars.get(idx + 1) == Some(&'0') =>
+            {
+                let zeros = chars[idx + 1..]
+                    .iter()
+                    .take_while(|&&other| other == '0')
+                    .count();
+                parts.push(Part::Fraction(zeros));
+                idx += 1 + zeros;
+                continue;
+            }
+            _ => {
+                parts.push(Part::Literal(ch.to_string()));
+                idx += 1;
+                continue;
+            }
+        }
+        idx += run;
+    }
+    parts
+}
+
+/// `m` means minutes right after hours or right before seconds, months otherwise.
+fn resolve_minutes(mut parts: Vec<Part>) -> Vec<Part> {
+    let fields: Vec<usize> = (0..parts.len())
+        .filter(|&idx| parts[idx].is_date_time())
+        .collect();
+    for (pos, &idx) in fields.iter().enumerate() {
+        let Part::MinuteOrMonth(width) = parts[idx] else {
+            continue;
+        };
+        let after_hours = pos
+            .checked_sub(1)
+            .map(|prev| &parts[fields[prev]])
+            .is_some_and(|prev| matches!(prev, Part::Hour(_) | Part::Elapsed('h', _)));
+        let before_seconds = fields
+            .get(pos + 1)
+            .is_some_and(|&next| matches!(parts[next], Part::Second(_) | Part::Elapsed('s', _)));
+        parts[idx] = if after_hours || before_seconds {
+            Part::Minute(width)
+        } else {
+            Part::Month(width)
+        };
+    }
+    parts
+}
+
+fn is_chinese_locale(code: &str) -> bool {
+    let code = code.to_ascii_lowercase();
+    code.contains("[$-804]") || code.contains("[$-zh") || code.contains("[dbnum")
+}
+
+fn pad(value: u32, width: usize) -> String {
+    if width >= 2 {
+        format!("{value:02}")
+    } else {
+        value.to_string()
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const MONTHS_CHINESE: [&str; 12] = [
+    "一月",
+    "二月",
+    "三月",
+    "四月",
+    "五月",
+    "六月",
+    "七月",
+    "八月",
+    "九月",
+    "十月",
+    "十一月",
+    "十二月",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+const WEEKDAYS_CHINESE: [&str; 7] = ["一", "二", "三", "四", "五", "六", "日"];
+
+fn month_text(month0: usize, width: usize, chinese: bool) -> String {
+    match width {
+        1 | 2 => pad(month0 as u32 + 1, width),
+        3 if chinese => format!("{}月", month0 + 1),
+        3 => MONTHS[month0][..3].to_string(),
+        4 if chinese => MONTHS_CHINESE[month0].to_string(),
+        4 => MONTHS[month0].to_string(),
+        _ if chinese => MONTHS_CHINESE[month0].chars().take(1).collect(),
+        _ => MONTHS[month0][..1].to_string(),
+    }
+}
+
+fn weekday_text(date_time: &NaiveDateTime, width: usize, chinese: bool) -> String {
+    let day = date_time.weekday().num_days_from_monday() as usize;
+    match (width, chinese) {
+        (3, true) => format!("周{}", WEEKDAYS_CHINESE[day]),
+        (_, true) => format!("星期{}", WEEKDAYS_CHINESE[day]),
+        (3, false) => WEEKDAYS[day][..3].to_string(),
+        (_, false) => WEEKDAYS[day].to_string(),
+    }
+}
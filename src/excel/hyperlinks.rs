@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use quick_xml::{Reader as XmlReader, events::Event};
+use rust_xlsxwriter::{Url, Worksheet};
+
+use super::{
+    ChunkPositions, open_xlsx_archive, parse_range_ref, read_relationships, read_sheet_xml,
+};
+
+/// A clickable link on one cell.
+#[derive(Debug, Clone)]
+pub struct Hyperlink {
+    pub row: usize,
+    pub col: usize,
+    /// Address understood by `rust_xlsxwriter`'s `Url`: `https://`, `mailto:` or `file:///`.
+    pub target: String,
+    pub tooltip: Option<String>,
+}
+
+/// Reads the hyperlinks of `sheet_name` from its `<hyperlinks>` element and the external
+/// targets in the sheet's `.rels` part. Positions are relative to `origin`, the sheet row and
+/// column of the first cell calamine returned; a link on a range is repeated on every cell of
+/// it inside the `total_rows` by `width` cells read. Links to places inside the workbook cannot
+/// follow the rows into the outputs and are dropped with a warning, as are addresses Excel
+/// cannot open or `rust_xlsxwriter` cannot write.
+pub fn extract_hyperlinks(
+    source: &Path,
+    sheet_name: &str,
+    origin: (usize, usize),
+    total_rows: usize,
+    width: usize,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Hyperlink>> {
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(Vec::new());
+    };
+    let (sheet_path, sheet_xml) = read_sheet_xml(&mut archive, sheet_name)?;
+    let entries = parse_hyperlinks(&sheet_xml);
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+    let targets: HashMap<String, String> = read_relationships(&mut archive, &sheet_path)
+        .into_iter()
+        .filter(|relationship| relationship.external)
+        .map(|relationship| (relationship.id, relationship.target))
+        .collect();
+
+    let mut links = Vec::new();
+    let mut dropped = 0;
+    let mut rejected = 0;
+    // Written once to a scratch sheet, which checks the address the way the outputs will.
+    let mut scratch = Worksheet::new();
+    for entry in entries {
+        let target = entry
+            .rel_id
+            .as_ref()
+            .and_then(|rel_id| targets.get(rel_id))
+            .and_then(|target| normalize_target(target));
+        let Some(mut target) = target else {
+            dropped += 1;
+            continue;
+        };
+        if let Some(location) = entry.location.filter(|location| !location.is_empty()) {
+            target = format!("{target}#{location}");
+        }
+        if scratch.write_url(0, 0, Url::new(target.as_str())).is_err() {
+            rejected += 1;
+            continue;
+        }
+        let Some(range) = parse_range_ref(&entry.reference) else {
+            continue;
+        };
+        for row in range.start_row.max(origin.0)..(range.end_row + 1).min(origin.0 + total_rows) {
+            for col in range.start_col.max(origin.1)..(range.end_col + 1).min(origin.1 + width) {
+                links.push(Hyperlink {
+                    row: row - origin.0,
+                    col: col - origin.1,
+                    target: target.clone(),
+                    tooltip: entry.tooltip.clone(),
+                });
+            }
+        }
+    }
+    if dropped > 0 {
+        warnings.push(format!(
+            "有 {dropped} 个超链接指向工作簿内部的位置或无法识别的地址，输出文件中未保留"
+        ));
+    }
+    if rejected > 0 {
+        warnings.push(format!(
+            "有 {rejected} 个超链接的地址过长或格式有误，无法写入输出文件，已保留单元格内容但去掉了链接"
+        ));
+    }
+    Ok(links)
+}
+
+/// The links of one output file at their positions in it.
+pub fn map_chunk_hyperlinks(links: &[Hyperlink], positions: &ChunkPositions) -> Vec<Hyperlink> {
+    links
+        .iter()
+        .filter_map(|link| {
+            let (row, col) = positions.cell(link.row, link.col)?;
+            Some(Hyperlink {
+                row: row as usize,
+                col: col as usize,
+                ..link.clone()
+            })
+        })
+        .collect()
+}
+
+/// One `<hyperlink>` element of the sheet XML.
+struct HyperlinkEntry {
+    reference: String,
+    rel_id: Option<String>,
+    location: Option<String>,
+    tooltip: Option<String>,
+}
+
+fn parse_hyperlinks(sheet_xml: &str) -> Vec<HyperlinkEntry> {
+    let mut reader = XmlReader::from_str(sheet_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut entries = Vec::new();
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) | Event::Empty(e) => {
+                if e.local_name().as_ref() != b"hyperlink" {
+                    continue;
+                }
+                let mut entry = HyperlinkEntry {
+                    reference: String::new(),
+                    rel_id: None,
+                    location: None,
+                    tooltip: None,
+                };
+                for attr in e.attributes().flatten() {
+                    let Ok(value) = attr.decode_and_unescape_value(&reader) else {
+                        continue;
+                    };
+                    let value = value.into_owned();
+                    match attr.key.as_ref() {
+                        b"ref" => entry.reference = value,
+                        b"location" => entry.location = Some(value),
+                        b"tooltip" => entry.tooltip = Some(value),
+                        key if key.ends_with(b":id") => entry.rel_id = Some(value),
+                        _ => {}
+                    }
+                }
+                entries.push(entry);
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    entries
+}
+
+/// Web and mail addresses are kept; file paths become `file:///` links. Other schemes
+/// (such as `tel:`) cannot be written and give `None`.
+fn normalize_target(target: &str) -> Option<String> {
+    let target = target.trim();
+    let lower = target.to_ascii_lowercase();
+    let known = [
+        "http://", "https://", "ftp://", "ftps://", "mailto:", "file://",
+    ]
+    .into_iter()
+    .find(|scheme| lower.starts_with(scheme));
+    if let Some(scheme) = known {
+        // `Url` only recognizes lower-case schemes.
+        return Some(format!("{scheme}{}", &target[scheme.len()..]));
+    }
+    // A drive letter (`C:\`) or a UNC path is not a scheme.
+    let scheme = target
+        .split_once(':')
+        .is_some_and(|(scheme, _)| scheme.len() > 1 && !scheme.contains(['\\', '/']));
+    if target.is_empty() || scheme {
+        None
+    } else {
+        Some(format!("file:///{target}"))
+    }
+}
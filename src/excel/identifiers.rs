@@ -0,0 +1,125 @@
+use calamine::Data;
+
+use super::{column_index_to_label, format_float, sheet_row_number};
+
+/// Header words that mark a column of identifiers rather than quantities.
+const IDENTIFIER_HINTS: &[&str] = &[
+    "身份证",
+    "证件",
+    "账号",
+    "帐号",
+    "账户",
+    "卡号",
+    "编号",
+    "号码",
+    "手机",
+    "电话",
+    "工号",
+    "学号",
+    "单号",
+    "邮编",
+    "邮政编码",
+    "社保",
+    "税号",
+    "信用代码",
+    "条码",
+];
+
+/// Numbers with at least this many digits are treated as identifiers (mobile numbers have 11).
+const IDENTIFIER_MIN_DIGITS: usize = 11;
+
+/// Excel keeps 15 significant digits; anything beyond was turned into zeros when typed in.
+const EXCEL_PRECISION_DIGITS: usize = 15;
+
+/// Marks the columns whose numbers are identifiers and must be written as text: columns whose
+/// header mentions 身份证, 账号, 编号 and the like, and columns of whole numbers where some
+/// value has 11 or more digits.
+pub fn detect_identifier_columns(header: &[Vec<String>], cells: &[Vec<Data>]) -> Vec<bool> {
+    let width = header
+        .iter()
+        .map(Vec::len)
+        .chain(cells.iter().map(Vec::len))
+        .max()
+        .unwrap_or(0);
+
+    (0..width)
+        .map(|col| {
+            let hinted = header.iter().filter_map(|row| row.get(col)).any(|text| {
+                IDENTIFIER_HINTS.iter().any(|hint| text.contains(hint))
+                    || text
+                        .split(|ch: char| !ch.is_ascii_alphanumeric())
+                        .any(|word| word.eq_ignore_ascii_case("id"))
+            });
+            if hinted {
+                return true;
+            }
+
+            let mut long = false;
+            for value in cells.iter().filter_map(|row| number(row.get(col)?)) {
+                if value.fract() != 0.0 {
+                    return false;
+                }
+                long |= digit_count(value) >= IDENTIFIER_MIN_DIGITS;
+            }
+            long
+        })
+        .collect()
+}
+
+/// One warning per column holding numbers longer than 15 digits: Excel already replaced their
+/// trailing digits with zeros, so they cannot be recovered from the file. Rows and columns are
+/// named by their sheet position; the table's first cell sits at `origin`.
+pub fn precision_warnings(
+    header: &[Vec<String>],
+    cells: &[Vec<Data>],
+    origin: (usize, usize),
+    warnings: &mut Vec<String>,
+) {
+    let header_rows = header.len();
+    let width = cells.iter().map(Vec::len).max().unwrap_or(0);
+    for col in 0..width {
+        let mut affected = cells
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, row)| match row.get(col)? {
+                Data::Float(value) if value.is_finite() => Some((idx, *value)),
+                _ => None,
+            })
+            .filter(|&(_, value)| digit_count(value) > EXCEL_PRECISION_DIGITS);
+        let Some((first_idx, first_value)) = affected.next() else {
+            continue;
+        };
+        let count = 1 + affected.count();
+        let name = header
+            .iter()
+            .rev()
+            .filter_map(|row| row.get(col))
+            .map(|text| text.trim())
+            .find(|text| !text.is_empty())
+            .map(|text| format!("“{text}”"))
+            .unwrap_or_else(|| format!("{} ", column_index_to_label(origin.1 + col)));
+        warnings.push(format!(
+            "{name}列有 {count} 个数值超过 15 位（如第 {} 行的 {}），Excel 只保存 15 位有效数字，\
+             源文件中的末尾数字已丢失，请将该列设为文本格式后重新录入",
+            sheet_row_number(origin.0, header_rows + first_idx),
+            format_float(first_value)
+        ));
+    }
+}
+
+fn number(cell: &Data) -> Option<f64> {
+    match cell {
+        Data::Float(f) if f.is_finite() => Some(*f),
+        Data::Int(i) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+fn digit_count(value: f64) -> usize {
+    let value = value.abs().trunc();
+    if value < 1.0 {
+        1
+    } else {
+        value.log10().floor() as usize + 1
+    }
+}
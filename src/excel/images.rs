@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use quick_xml::{Reader as XmlReader, events::Event};
+use rust_xlsxwriter::{Image, ObjectMovement};
+
+use super::{
+    ChunkPositions, open_xlsx_archive, read_relationships, read_sheet_xml, read_zip_bytes,
+    read_zip_entry,
+};
+
+/// Drawing sizes and offsets are in EMUs; Excel draws 9525 of them per pixel.
+const EMU_PER_PIXEL: f64 = 9525.0;
+
+/// A picture placed on the sheet, anchored at the top-left corner of one cell.
+#[derive(Clone)]
+pub struct SheetImage {
+    pub row: usize,
+    pub col: usize,
+    /// Offset of the picture inside its cell, in pixels.
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub image: Image,
+}
+
+/// Reads the pictures of `sheet_name` from the drawing part the sheet's relationships point to,
+/// with their size, alternative text and how they move with the cells. Anchor cells are
+/// relative to `origin`, the sheet row and column of the first cell calamine returned; pictures
+/// above or left of it are anchored in the first row or column. Pictures in formats
+/// `rust_xlsxwriter` cannot write (such as EMF), in groups or not anchored to a cell are
+/// dropped with a warning.
+pub fn extract_images(
+    source: &Path,
+    sheet_name: &str,
+    origin: (usize, usize),
+    warnings: &mut Vec<String>,
+) -> Result<Vec<SheetImage>> {
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(Vec::new());
+    };
+    let (sheet_path, _) = read_sheet_xml(&mut archive, sheet_name)?;
+    let Some(drawing_path) = read_relationships(&mut archive, &sheet_path)
+        .into_iter()
+        .find(|relationship| relationship.kind == "drawing" && !relationship.external)
+        .map(|relationship| relationship.target)
+    else {
+        return Ok(Vec::new());
+    };
+    let drawing_xml = read_zip_entry(&mut archive, &drawing_path)?;
+    let targets: HashMap<String, String> = read_relationships(&mut archive, &drawing_path)
+        .into_iter()
+        .filter(|relationship| relationship.kind == "image" && !relationship.external)
+        .map(|relationship| (relationship.id, relationship.target))
+        .collect();
+
+    let mut images = Vec::new();
+    let mut dropped = 0;
+    for anchor in parse_drawing(&drawing_xml) {
+        let image = anchor
+            .cell
+            .filter(|_| !anchor.grouped)
+            .zip(anchor.embed.as_ref().and_then(|embed| targets.get(embed)))
+            .and_then(|(cell, target)| {
+                let bytes = read_zip_bytes(&mut archive, target).ok()?;
+                let image = Image::new_from_buffer(&bytes).ok()?;
+                Some((cell, image))
+            });
+        let Some((cell, mut image)) = image else {
+            dropped += 1;
+            continue;
+        };
+        if let Some((width, height)) = anchor.size {
+            image = image.set_scale_to_size(
+                width as f64 / EMU_PER_PIXEL,
+                height as f64 / EMU_PER_PIXEL,
+                false,
+            );
+        }
+        if let Some(alt_text) = anchor.alt_text.filter(|text| !text.is_empty()) {
+            image = image.set_alt_text(alt_text);
+        }
+        image = image.set_object_movement(match anchor.edit_as.as_deref() {
+            Some("oneCell") => ObjectMovement::MoveButDontSizeWithCells,
+            Some("absolute") => ObjectMovement::DontMoveOrSizeWithCells,
+            _ => ObjectMovement::MoveAndSizeWithCells,
+        });
+        images.push(SheetImage {
+            row: cell.row.saturating_sub(origin.0),
+            col: cell.col.saturating_sub(origin.1),
+            x_offset: (cell.col_offset as f64 / EMU_PER_PIXEL).round() as u32,
+            y_offset: (cell.row_offset as f64 / EMU_PER_PIXEL).round() as u32,
+            image,
+        });
+    }
+    if dropped > 0 {
+        warnings.push(format!(
+            "有 {dropped} 张图片格式不受支持、属于组合图形或未固定在单元格上，输出文件中未保留"
+        ));
+    }
+    Ok(images)
+}
+
+/// The pictures of one output file at their positions in it; header pictures appear in
+/// every file.
+pub fn map_chunk_images(images: &[SheetImage], positions: &ChunkPositions) -> Vec<SheetImage> {
+    images
+        .iter()
+        .filter_map(|image| {
+            let (row, col) = positions.cell(image.row, image.col)?;
+            Some(SheetImage {
+                row: row as usize,
+                col: col as usize,
+                ..image.clone()
+            })
+        })
+        .collect()
+}
+
+/// The top-left corner of an anchor: a sheet cell and the offsets inside it, in EMUs.
+#[derive(Default, Clone, Copy)]
+struct AnchorCell {
+    row: usize,
+    col: usize,
+    row_offset: u64,
+    col_offset: u64,
+}
+
+/// One anchor (`<xdr:twoCellAnchor>`, `<xdr:oneCellAnchor>` or `<xdr:absoluteAnchor>`) of
+/// the drawing that holds a picture.
+#[derive(Default)]
+struct AnchorEntry {
+    /// `None` for anchors placed at an absolute position.
+    cell: Option<AnchorCell>,
+    edit_as: Option<String>,
+    /// Relationship id of the picture's image part.
+    embed: Option<String>,
+    /// Width and height in EMUs.
+    size: Option<(u64, u64)>,
+    alt_text: Option<String>,
+    grouped: bool,
+}
+
+/// The part of the `<xdr:from>` element whose text is being read.
+#[derive(Clone, Copy)]
+enum FromField {
+    Col,
+    ColOffset,
+    Row,
+    RowOffset,
+}
+
+fn parse_drawing(drawing_xml: &str) -> Vec<AnchorEntry> {
+    let mut reader = XmlReader::from_str(drawing_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut anchors = Vec::new();
+    let mut current: Option<AnchorEntry> = None;
+    let mut has_picture = false;
+    let mut in_from = false;
+    let mut field = None;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) | Event::Empty(e) => {
+                let attribute = |name: &[u8]| {
+                    e.attributes()
+                        .flatten()
+                        .find(|attr| attr.key.local_name().as_ref() == name)
+                        .and_then(|attr| attr.decode_and_unescape_value(&reader).ok())
+                        .map(|value| value.into_owned())
+                };
+                match e.local_name().as_ref() {
+                    name @ (b"twoCellAnchor" | b"oneCellAnchor" | b"absoluteAnchor") => {
+                        current = Some(AnchorEntry {
+                            cell: (name != b"absoluteAnchor").then(AnchorCell::default),
+                            edit_as: attribute(b"editAs"),
+                            ..AnchorEntry::default()
+                        });
+                        has_picture = false;
+                    }
+                    b"from" => in_from = true,
+                    b"col" if in_from => field = Some(FromField::Col),
+                    b"colOff" if in_from => field = Some(FromField::ColOffset),
+                    b"row" if in_from => field = Some(FromField::Row),
+                    b"rowOff" if in_from => field = Some(FromField::RowOffset),
+                    b"pic" => has_picture = true,
+                    b"grpSp" => {
+                        if let Some(anchor) = current.as_mut() {
+                            anchor.grouped = true;
+                        }
+                    }
+                    b"cNvPr" if has_picture => {
+                        if let Some(anchor) = current.as_mut() {
+                            anchor.alt_text = attribute(b"descr");
+                        }
+                    }
+                    b"blip" => {
+                        if let Some(anchor) = current.as_mut() {
+                            anchor.embed = attribute(b"embed");
+                        }
+                    }
+                    // The anchor's own `<xdr:ext>` or the picture's `<a:xfrm><a:ext>`.
+                    b"ext" => {
+                        let size = attribute(b"cx")
+                            .zip(attribute(b"cy"))
+                            .and_then(|(cx, cy)| Some((cx.parse().ok()?, cy.parse().ok()?)));
+                        if let (Some(anchor), Some(size)) = (current.as_mut(), size) {
+                            anchor.size = Some(size);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"twoCellAnchor" | b"oneCellAnchor" | b"absoluteAnchor" => {
+                    if let Some(anchor) = current.take().filter(|_| has_picture) {
+                        anchors.push(anchor);
+                    }
+                }
+                b"from" => in_from = false,
+                b"col" | b"colOff" | b"row" | b"rowOff" => field = None,
+                _ => {}
+            },
+            Event::Text(text) => {
+                let (Some(field), Some(cell)) = (
+                    field,
+                    current.as_mut().and_then(|anchor| anchor.cell.as_mut()),
+                ) else {
+                    continue;
+                };
+                let Ok(value) = text.unescape() else {
+                    continue;
+                };
+                let value: u64 = value.trim().parse().unwrap_or(0);
+                match field {
+                    FromField::Col => cell.col = value as usize,
+                    FromField::ColOffset => cell.col_offset = value,
+                    FromField::Row => cell.row = value as usize,
+                    FromField::RowOffset => cell.row_offset = value,
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    anchors
+}
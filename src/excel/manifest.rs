@@ -0,0 +1,159 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{SplitOptions, SplitResult};
+
+/// Machine-readable record of a split job, written next to the generated files.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub generated_at: String,
+    pub source: ManifestSource,
+    pub options: SplitOptions,
+    pub total_rows: usize,
+    pub header_rows: usize,
+    /// Data rows excluded by the filter expression in `options`.
+    #[serde(default)]
+    pub filtered_rows: usize,
+    /// Hidden data rows left out because of `drop_hidden` in `options`.
+    #[serde(default)]
+    pub hidden_rows: usize,
+    /// Data rows removed as duplicates and the report listing them, relative like `file`.
+    #[serde(default)]
+    pub duplicate_rows: usize,
+    #[serde(default)]
+    pub duplicates_file: Option<PathBuf>,
+    pub chunks: Vec<ManifestChunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestSource {
+    pub path: PathBuf,
+    pub sha256: String,
+    pub sheet: String,
+}
+
+/// One output file. `file` is relative to the manifest's directory and the row
+/// numbers are 1-based sheet rows of the source, matching what Excel shows.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestChunk {
+    pub index: usize,
+    pub file: PathBuf,
+    pub source_start_row: Option<usize>,
+    pub source_end_row: Option<usize>,
+    pub total_rows: usize,
+    pub data_rows: usize,
+    pub group_key: Option<String>,
+    pub sha256: String,
+}
+
+/// Raised whenever the recorded options change shape; manifests of other versions cannot be
+/// read back.
+const MANIFEST_VERSION: u32 = 2;
+
+/// Just the version, read before the rest so an old manifest gets a clear message rather than
+/// a parse error about its options.
+#[derive(Deserialize)]
+struct ManifestHeader {
+    version: u32,
+}
+
+pub fn build_manifest_path(source: &Path) -> PathBuf {
+    let parent = source
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("split");
+    parent.join(format!("{stem}_manifest.json"))
+}
+
+pub fn write_manifest(
+    source: &Path,
+    options: &SplitOptions,
+    result: &SplitResult,
+) -> Result<PathBuf> {
+    let destination = build_manifest_path(source);
+    let base_dir = destination.parent().unwrap_or_else(|| Path::new("."));
+
+    let chunks = result
+        .chunks
+        .iter()
+        .enumerate()
+        .map(|(idx, chunk)| ManifestChunk {
+            index: idx + 1,
+            file: relative_to(&chunk.file_path, base_dir),
+            source_start_row: chunk.source_rows.map(|(start, _)| start),
+            source_end_row: chunk.source_rows.map(|(_, end)| end),
+            total_rows: chunk.total_rows,
+            data_rows: chunk.data_rows,
+            group_key: chunk.group_key.clone(),
+            sha256: chunk.sha256.clone(),
+        })
+        .collect();
+
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        generated_at: chrono::Local::now().to_rfc3339(),
+        source: ManifestSource {
+            path: source.to_path_buf(),
+            sha256: sha256_file(source)?,
+            sheet: result.sheet_name.clone(),
+        },
+        options: options.clone(),
+        total_rows: result.total_rows,
+        header_rows: result.header_rows,
+        filtered_rows: result.filtered_rows,
+        hidden_rows: result.hidden_rows,
+        duplicate_rows: result.duplicate_rows,
+        duplicates_file: result
+            .duplicates_path
+            .as_ref()
+            .map(|path| relative_to(path, base_dir)),
+        chunks,
+    };
+
+    let file = File::create(&destination)
+        .with_context(|| format!("无法创建清单文件: {}", destination.display()))?;
+    serde_json::to_writer_pretty(file, &manifest)
+        .with_context(|| format!("无法写入清单文件: {}", destination.display()))?;
+    Ok(destination)
+}
+
+/// Reads a manifest written by this version of the tool.
+pub fn read_manifest(path: &Path) -> Result<Manifest> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("无法打开清单文件: {}", path.display()))?;
+    let header: ManifestHeader = serde_json::from_str(&text)
+        .with_context(|| format!("无法解析清单文件: {}", path.display()))?;
+    if header.version != MANIFEST_VERSION {
+        return Err(anyhow!(
+            "清单文件 {} 的版本为 {}，本程序只能读取版本 {MANIFEST_VERSION} 的清单，请重新拆分后再校验",
+            path.display(),
+            header.version
+        ));
+    }
+    serde_json::from_str(&text).with_context(|| format!("无法解析清单文件: {}", path.display()))
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .with_context(|| format!("无法读取文件以计算校验值: {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("无法读取文件以计算校验值: {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn relative_to(path: &Path, base_dir: &Path) -> PathBuf {
+    path.strip_prefix(base_dir)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
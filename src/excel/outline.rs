@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::Result;
+use quick_xml::{Reader as XmlReader, events::Event};
+use rust_xlsxwriter::Worksheet;
+
+use super::{ChunkPositions, open_xlsx_archive, read_sheet_xml, runs};
+
+/// Deepest outline level Excel supports.
+const MAX_OUTLINE_LEVEL: u8 = 7;
+
+/// Whether a row or column is hidden and how deep it sits in the outline groups.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LineOutline {
+    pub hidden: bool,
+    pub level: u8,
+    /// Set on the summary row or column of a collapsed group.
+    pub collapsed: bool,
+}
+
+/// Hidden rows and columns and the outline groups of the source sheet.
+#[derive(Default)]
+pub struct SheetOutline {
+    /// By read-range row; rows without any of the attributes are left out.
+    rows: HashMap<usize, LineOutline>,
+    /// By read-range column, for the columns of the read range.
+    columns: HashMap<usize, LineOutline>,
+    /// The summary row of a group sits above its rows instead of below.
+    summary_above: bool,
+    /// The summary column of a group sits left of its columns instead of right.
+    summary_left: bool,
+}
+
+impl SheetOutline {
+    pub fn row_hidden(&self, row: usize) -> bool {
+        self.rows.get(&row).is_some_and(|line| line.hidden)
+    }
+
+    pub fn column_hidden(&self, col: usize) -> bool {
+        self.columns.get(&col).is_some_and(|line| line.hidden)
+    }
+}
+
+/// The hidden rows and columns and outline groups of one output file, by output position.
+#[derive(Default)]
+pub struct ChunkOutline {
+    rows: BTreeMap<u32, LineOutline>,
+    columns: BTreeMap<u32, LineOutline>,
+    summary_above: bool,
+    summary_left: bool,
+}
+
+/// Reads the `hidden`, `outlineLevel` and `collapsed` attributes of the `<row>` and `<col>`
+/// elements of `sheet_name`. Positions are relative to `origin`, the sheet row and column of the
+/// first cell calamine returned, and limited to the `total_rows` rows and `width` columns read.
+pub fn extract_outline(
+    source: &Path,
+    sheet_name: &str,
+    origin: (usize, usize),
+    total_rows: usize,
+    width: usize,
+) -> Result<SheetOutline> {
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(SheetOutline::default());
+    };
+    let (_, sheet_xml) = read_sheet_xml(&mut archive, sheet_name)?;
+
+    let mut reader = XmlReader::from_str(&sheet_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut outline = SheetOutline::default();
+    // `<row>` elements may leave out `r` and then follow the previous row.
+    let mut next_row = 0;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            // Cells carry no outline attributes and are most of the sheet, so they are skipped
+            // before their attributes are read.
+            Event::Start(e) | Event::Empty(e)
+                if matches!(e.local_name().as_ref(), b"row" | b"col" | b"outlinePr") =>
+            {
+                let mut line = LineOutline::default();
+                let mut position = None;
+                let mut last = None;
+                let mut summary_below = true;
+                let mut summary_right = true;
+                for attr in e.attributes().flatten() {
+                    let Ok(value) = attr.decode_and_unescape_value(&reader) else {
+                        continue;
+                    };
+                    let flag = value == "1" || value == "true";
+                    match attr.key.local_name().as_ref() {
+                        b"hidden" => line.hidden = flag,
+                        b"collapsed" => line.collapsed = flag,
+                        b"outlineLevel" => {
+                            line.level = value.parse().unwrap_or(0).min(MAX_OUTLINE_LEVEL)
+                        }
+                        b"r" | b"min" => position = value.parse::<usize>().ok(),
+                        b"max" => last = value.parse::<usize>().ok(),
+                        b"summaryBelow" => summary_below = flag,
+                        b"summaryRight" => summary_right = flag,
+                        _ => {}
+                    }
+                }
+                match e.local_name().as_ref() {
+                    b"outlinePr" => {
+                        outline.summary_above = !summary_below;
+                        outline.summary_left = !summary_right;
+                    }
+                    b"col" => {
+                        let Some(first) = position.filter(|_| line != LineOutline::default())
+                        else {
+                            continue;
+                        };
+                        // `min` and `max` are 1-based sheet columns.
+                        let Some(last) = last.unwrap_or(first).checked_sub(origin.1 + 1) else {
+                            continue;
+                        };
+                        let first = first.saturating_sub(origin.1 + 1);
+                        for col in first..=last.min(width.saturating_sub(1)) {
+                            outline.columns.insert(col, line);
+                        }
+                    }
+                    b"row" => {
+                        let row = position.map_or(next_row, |r| r.saturating_sub(1));
+                        next_row = row + 1;
+                        if line != LineOutline::default()
+                            && let Some(row) = row.checked_sub(origin.0)
+                            && row < total_rows
+                        {
+                            outline.rows.insert(row, line);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"sheetData" => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(outline)
+}
+
+/// The hidden rows and columns and outline groups of one output file at their positions in it.
+pub fn map_chunk_outline(outline: &SheetOutline, positions: &ChunkPositions) -> ChunkOutline {
+    ChunkOutline {
+        rows: outline
+            .rows
+            .iter()
+            .filter_map(|(&row, &line)| Some((positions.row(row)?, line)))
+            .collect(),
+        columns: outline
+            .columns
+            .iter()
+            .filter_map(|(&col, &line)| Some((u32::from(positions.column(col)?), line)))
+            .collect(),
+        summary_above: outline.summary_above,
+        summary_left: outline.summary_left,
+    }
+}
+
+/// Groups and hides the rows and columns of `outline`. Runs of rows that are no longer adjacent
+/// in the output become separate groups.
+pub fn write_outline(worksheet: &mut Worksheet, outline: &ChunkOutline) -> Result<()> {
+    for (first, last, collapsed) in groups(&outline.rows, !outline.summary_above) {
+        if collapsed {
+            worksheet.group_rows_collapsed(first, last)?;
+        } else {
+            worksheet.group_rows(first, last)?;
+        }
+    }
+    for (first, last, collapsed) in groups(&outline.columns, !outline.summary_left) {
+        let (first, last) = (first as u16, last as u16);
+        if collapsed {
+            worksheet.group_columns_collapsed(first, last)?;
+        } else {
+            worksheet.group_columns(first, last)?;
+        }
+    }
+    for (&row, line) in &outline.rows {
+        if line.hidden {
+            worksheet.set_row_hidden(row)?;
+        }
+    }
+    for (&col, line) in &outline.columns {
+        if line.hidden {
+            worksheet.set_column_hidden(col as u16)?;
+        }
+    }
+    worksheet.group_symbols_above(outline.summary_above);
+    worksheet.group_symbols_to_left(outline.summary_left);
+    Ok(())
+}
+
+/// First and last position of every group, level by level, and whether it is collapsed: all of
+/// its lines hidden and the summary line after it marked collapsed. `rust_xlsxwriter` only marks
+/// the line after a group, so groups with the summary before them are written expanded and
+/// their lines merely hidden.
+fn groups(lines: &BTreeMap<u32, LineOutline>, summary_after: bool) -> Vec<(u32, u32, bool)> {
+    let max_level = lines.values().map(|line| line.level).max().unwrap_or(0);
+    (1..=max_level)
+        .flat_map(|level| {
+            runs(
+                lines
+                    .iter()
+                    .filter(|(_, line)| line.level >= level)
+                    .map(|(&position, _)| position),
+            )
+            .into_iter()
+            .map(|(first, last)| {
+                let collapsed = summary_after
+                    && lines.get(&(last + 1)).is_some_and(|line| line.collapsed)
+                    && (first..=last).all(|position| lines[&position].hidden);
+                (first, last, collapsed)
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,85 @@
+use calamine::Data;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Calendar period used to bucket rows in a date split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DatePeriod {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl DatePeriod {
+    pub const ALL: [DatePeriod; 5] = [
+        DatePeriod::Day,
+        DatePeriod::Week,
+        DatePeriod::Month,
+        DatePeriod::Quarter,
+        DatePeriod::Year,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DatePeriod::Day => "按日",
+            DatePeriod::Week => "按周",
+            DatePeriod::Month => "按月",
+            DatePeriod::Quarter => "按季度",
+            DatePeriod::Year => "按年",
+        }
+    }
+
+    /// Name of the period containing `date`, also used in the output file name. The keys sort
+    /// chronologically as plain strings (`2024-03-01`, `2024-W09`, `2024-03`, `2024-Q1`, `2024`).
+    pub fn key(self, date: NaiveDate) -> String {
+        match self {
+            DatePeriod::Day => date.format("%Y-%m-%d").to_string(),
+            DatePeriod::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            DatePeriod::Month => date.format("%Y-%m").to_string(),
+            DatePeriod::Quarter => format!("{}-Q{}", date.year(), date.month0() / 3 + 1),
+            DatePeriod::Year => date.year().to_string(),
+        }
+    }
+}
+
+/// Date of a cell stored as an Excel date, an ISO 8601 string or date-like text.
+pub fn cell_date(value: &Data) -> Option<NaiveDate> {
+    match value {
+        Data::DateTime(dt) if dt.is_datetime() => dt.as_datetime().map(|dt| dt.date()),
+        Data::DateTimeIso(text) | Data::String(text) => parse_text_date(text),
+        _ => None,
+    }
+}
+
+/// Parses the date part of text such as `2024-03-01`, `2024/3/1 08:30`, `2024.3.1`,
+/// `20240301`, `2024年3月1日` or `2024年3月` (the first day of the month).
+pub fn parse_text_date(text: &str) -> Option<NaiveDate> {
+    let date_part = text
+        .trim()
+        .split(|ch: char| ch.is_whitespace() || ch == 'T')
+        .next()?;
+    if !date_part
+        .chars()
+        .all(|ch| ch.is_ascii_digit() || matches!(ch, '-' | '/' | '.' | '年' | '月' | '日' | '号'))
+    {
+        return None;
+    }
+
+    let numbers: Vec<&str> = date_part
+        .split(|ch: char| !ch.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .collect();
+    let (year, month, day) = match numbers.as_slice() {
+        [compact] if compact.len() == 8 => (&compact[..4], &compact[4..6], &compact[6..]),
+        [year, month] if year.len() == 4 => (*year, *month, "1"),
+        [year, month, day] if year.len() == 4 => (*year, *month, *day),
+        _ => return None,
+    };
+
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+}
@@ -0,0 +1,221 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use quick_xml::{Reader as XmlReader, events::Event};
+use rust_xlsxwriter::Worksheet;
+
+use super::{open_xlsx_archive, read_sheet_xml};
+
+/// Placeholder in the footer option replaced by the number of the output file.
+pub const INDEX_PLACEHOLDER: &str = "{index}";
+
+/// Excel's limit for the header or footer text.
+pub const MAX_HEADER_FOOTER_CHARS: usize = 255;
+
+/// The page setup of the source sheet and what is printed around every page.
+#[derive(Debug, Default, Clone)]
+pub struct PrintSetup {
+    landscape: bool,
+    paper_size: Option<u8>,
+    /// Left, right, top, bottom, header and footer margins in inches.
+    margins: Option<[f64; 6]>,
+    scale: Option<u16>,
+    /// Pages wide and tall the sheet is fitted to; 0 leaves that direction unlimited.
+    fit_to_pages: Option<(u16, u16)>,
+    over_then_down: bool,
+    center_horizontally: bool,
+    center_vertically: bool,
+    gridlines: bool,
+    headings: bool,
+    black_and_white: bool,
+    draft: bool,
+    header: Option<String>,
+    footer: Option<String>,
+    /// Rows at the top repeated on every printed page.
+    pub repeat_rows: usize,
+}
+
+/// Reads the page setup, margins, print options and the header and footer of `sheet_name`.
+/// Pictures in the header or footer are not kept and reported in `warnings`.
+pub fn extract_print_setup(
+    source: &Path,
+    sheet_name: &str,
+    warnings: &mut Vec<String>,
+) -> Result<PrintSetup> {
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(PrintSetup::default());
+    };
+    let (_, sheet_xml) = read_sheet_xml(&mut archive, sheet_name)?;
+
+    let mut reader = XmlReader::from_str(&sheet_xml);
+    let mut buf = Vec::new();
+    let mut setup = PrintSetup::default();
+    let mut fit_to_page = false;
+    let mut fit_to_pages = (1, 1);
+    // The `<oddHeader>` or `<oddFooter>` being read; Excel shows them on every page unless
+    // first or even pages have their own.
+    let mut text: Option<(bool, String)> = None;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) | Event::Empty(e) => {
+                let name = e.local_name();
+                match name.as_ref() {
+                    b"oddHeader" => text = Some((true, String::new())),
+                    b"oddFooter" => text = Some((false, String::new())),
+                    _ => {}
+                }
+                for attr in e.attributes().flatten() {
+                    let Ok(value) = attr.decode_and_unescape_value(&reader) else {
+                        continue;
+                    };
+                    let flag = value == "1" || value == "true";
+                    match (name.as_ref(), attr.key.local_name().as_ref()) {
+                        (b"pageSetUpPr", b"fitToPage") => fit_to_page = flag,
+                        (b"printOptions", b"horizontalCentered") => {
+                            setup.center_horizontally = flag
+                        }
+                        (b"printOptions", b"verticalCentered") => setup.center_vertically = flag,
+                        (b"printOptions", b"gridLines") => setup.gridlines = flag,
+                        (b"printOptions", b"headings") => setup.headings = flag,
+                        (b"pageMargins", side) => {
+                            let index = match side {
+                                b"left" => 0,
+                                b"right" => 1,
+                                b"top" => 2,
+                                b"bottom" => 3,
+                                b"header" => 4,
+                                b"footer" => 5,
+                                _ => continue,
+                            };
+                            if let Ok(inches) = value.parse() {
+                                setup.margins.get_or_insert([-1.0; 6])[index] = inches;
+                            }
+                        }
+                        (b"pageSetup", b"orientation") => setup.landscape = value == "landscape",
+                        (b"pageSetup", b"paperSize") => setup.paper_size = value.parse().ok(),
+                        (b"pageSetup", b"scale") => {
+                            setup.scale = value.parse().ok().filter(|&scale| scale != 100)
+                        }
+                        (b"pageSetup", b"fitToWidth") => {
+                            fit_to_pages.0 = value.parse().unwrap_or(1)
+                        }
+                        (b"pageSetup", b"fitToHeight") => {
+                            fit_to_pages.1 = value.parse().unwrap_or(1)
+                        }
+                        (b"pageSetup", b"pageOrder") => {
+                            setup.over_then_down = value == "overThenDown"
+                        }
+                        (b"pageSetup", b"blackAndWhite") => setup.black_and_white = flag,
+                        (b"pageSetup", b"draft") => setup.draft = flag,
+                        _ => {}
+                    }
+                }
+            }
+            Event::Text(e) => {
+                if let Some((_, content)) = text.as_mut()
+                    && let Ok(value) = e.unescape()
+                {
+                    content.push_str(&value);
+                }
+            }
+            Event::End(e) if matches!(e.local_name().as_ref(), b"oddHeader" | b"oddFooter") => {
+                if let Some((is_header, content)) = text.take() {
+                    let content = Some(content).filter(|content| !content.is_empty());
+                    if is_header {
+                        setup.header = content;
+                    } else {
+                        setup.footer = content;
+                    }
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    if fit_to_page {
+        setup.fit_to_pages = Some(fit_to_pages);
+        setup.scale = None;
+    }
+
+    let mut has_picture = false;
+    for text in [&mut setup.header, &mut setup.footer].into_iter().flatten() {
+        if text.contains("&G") {
+            has_picture = true;
+            *text = text.replace("&G", "");
+        }
+    }
+    if has_picture {
+        warnings.push("源工作表页眉或页脚中的图片无法在输出文件中保留".to_string());
+    }
+    Ok(setup)
+}
+
+impl PrintSetup {
+    /// Replaces the source footer, when given, with `footer` where [`INDEX_PLACEHOLDER`] is
+    /// still to be filled in per output file.
+    pub fn with_footer(mut self, footer: Option<&str>) -> Self {
+        if let Some(footer) = footer.map(str::trim).filter(|footer| !footer.is_empty()) {
+            self.footer = Some(footer.to_string());
+        }
+        self
+    }
+
+    /// The setup of output number `index`, repeating its `header_rows` on every page. Fails
+    /// when the footer with the number filled in is longer than Excel allows.
+    pub fn for_chunk(&self, index: usize, header_rows: usize) -> Result<Self> {
+        let footer = self
+            .footer
+            .as_ref()
+            .map(|footer| footer.replace(INDEX_PLACEHOLDER, &index.to_string()));
+        if let Some(footer) = &footer
+            && footer.chars().count() > MAX_HEADER_FOOTER_CHARS
+        {
+            return Err(anyhow!(
+                "第 {index} 个输出文件的页脚填入序号后超过 {MAX_HEADER_FOOTER_CHARS} 个字符"
+            ));
+        }
+        Ok(Self {
+            footer,
+            repeat_rows: header_rows,
+            ..self.clone()
+        })
+    }
+}
+
+pub fn write_print_setup(worksheet: &mut Worksheet, setup: &PrintSetup) -> Result<()> {
+    if setup.landscape {
+        worksheet.set_landscape();
+    }
+    if let Some(paper_size) = setup.paper_size {
+        worksheet.set_paper_size(paper_size);
+    }
+    if let Some([left, right, top, bottom, header, footer]) = setup.margins {
+        worksheet.set_margins(left, right, top, bottom, header, footer);
+    }
+    if let Some(scale) = setup.scale {
+        worksheet.set_print_scale(scale);
+    }
+    if let Some((width, height)) = setup.fit_to_pages {
+        worksheet.set_print_fit_to_pages(width, height);
+    }
+    if setup.over_then_down {
+        worksheet.set_page_order(false);
+    }
+    worksheet.set_print_center_horizontally(setup.center_horizontally);
+    worksheet.set_print_center_vertically(setup.center_vertically);
+    worksheet.set_print_gridlines(setup.gridlines);
+    worksheet.set_print_headings(setup.headings);
+    worksheet.set_print_black_and_white(setup.black_and_white);
+    worksheet.set_print_draft(setup.draft);
+    if let Some(header) = &setup.header {
+        worksheet.set_header(header);
+    }
+    if let Some(footer) = &setup.footer {
+        worksheet.set_footer(footer);
+    }
+    if setup.repeat_rows > 0 {
+        worksheet.set_repeat_rows(0, setup.repeat_rows as u32 - 1)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use anyhow::Result;
+use quick_xml::{Reader as XmlReader, events::Event};
+use rust_xlsxwriter::DocProperties;
+
+use super::print::INDEX_PLACEHOLDER;
+use super::{open_xlsx_archive, read_zip_entry};
+
+/// Placeholder in the sheet name option replaced by the name of the source sheet.
+const SHEET_PLACEHOLDER: &str = "{sheet}";
+
+/// Placeholder in the sheet name option replaced by the group key of the output file, or its
+/// number when it has none.
+const KEY_PLACEHOLDER: &str = "{key}";
+
+/// Excel's limit for a sheet name.
+const MAX_SHEET_NAME_CHARS: usize = 31;
+
+/// Reads the core (`docProps/core.xml`) and application (`docProps/app.xml`) properties of the
+/// source workbook that the outputs keep: title, subject, author, manager, company, category,
+/// keywords and comments. Parts that are missing leave their properties empty.
+pub fn extract_properties(source: &Path) -> Result<DocProperties> {
+    let mut properties = DocProperties::new();
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(properties);
+    };
+    for path in ["docProps/core.xml", "docProps/app.xml"] {
+        let Ok(xml) = read_zip_entry(&mut archive, path) else {
+            continue;
+        };
+        let mut reader = XmlReader::from_str(&xml);
+        let mut buf = Vec::new();
+        let mut current: Option<Vec<u8>> = None;
+        while let Ok(event) = reader.read_event_into(&mut buf) {
+            match event {
+                Event::Eof => break,
+                Event::Start(e) => current = Some(e.local_name().as_ref().to_vec()),
+                Event::Text(text) => {
+                    let (Some(name), Ok(value)) = (current.as_deref(), text.unescape()) else {
+                        continue;
+                    };
+                    let value = value.trim();
+                    if value.is_empty() {
+                        continue;
+                    }
+                    properties = match name {
+                        b"title" => properties.set_title(value),
+                        b"subject" => properties.set_subject(value),
+                        b"creator" => properties.set_author(value),
+                        b"Manager" => properties.set_manager(value),
+                        b"Company" => properties.set_company(value),
+                        b"category" => properties.set_category(value),
+                        b"keywords" => properties.set_keywords(value),
+                        b"description" => properties.set_comment(value),
+                        _ => properties,
+                    };
+                }
+                Event::End(_) => current = None,
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+    Ok(properties)
+}
+
+/// The properties of output number `index`: the source's, plus custom properties naming the
+/// source file and the 1-based sheet rows its data came from.
+pub fn chunk_properties(
+    properties: &DocProperties,
+    source: &Path,
+    index: usize,
+    source_rows: Option<(usize, usize)>,
+) -> DocProperties {
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let output = properties
+        .clone()
+        .set_custom_property("源文件", file_name)
+        .set_custom_property("拆分序号", i32::try_from(index).unwrap_or(i32::MAX));
+    match source_rows {
+        Some((first, last)) => output.set_custom_property("源数据行", format!("{first}-{last}")),
+        None => output,
+    }
+}
+
+/// Name of the sheet in output number `index`: `template` with its placeholders filled in, or
+/// the source sheet name without one. Characters Excel does not allow in sheet names are
+/// replaced and the name is cut to Excel's limit.
+pub fn output_sheet_name(
+    template: Option<&str>,
+    sheet_name: &str,
+    index: usize,
+    group_key: Option<&str>,
+) -> String {
+    let index = index.to_string();
+    let name = match template.map(str::trim).filter(|text| !text.is_empty()) {
+        Some(template) => template
+            .replace(SHEET_PLACEHOLDER, sheet_name)
+            .replace(INDEX_PLACEHOLDER, &index)
+            .replace(KEY_PLACEHOLDER, group_key.unwrap_or(&index)),
+        None => sheet_name.to_string(),
+    };
+    let name: String = name
+        .chars()
+        .map(|ch| match ch {
+            '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '_',
+            ch if ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect();
+    let name: String = name
+        .trim_matches('\'')
+        .chars()
+        .take(MAX_SHEET_NAME_CHARS)
+        .collect();
+    let name = name.trim_end_matches('\'');
+    // Excel reserves "History" for its change tracking.
+    if name.is_empty() || name.eq_ignore_ascii_case("history") {
+        format!("Sheet{index}")
+    } else {
+        name.to_string()
+    }
+}
@@ -0,0 +1,179 @@
+use std::ops::Range;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use super::sheet_row_number;
+
+/// Sheet rows (1-based, as shown by Excel) that go into one output file. `end` is inclusive;
+/// a `None` start means "from the first data row" and a `None` end "to the last row".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowRange {
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+/// Parses either a list of ranges such as `2-120, 121-400, 401-` or a list of cut points such
+/// as `121, 401`, where every number starts a new file. Entries may be separated by commas,
+/// semicolons, whitespace or new lines, so the content of a small text file works as well.
+/// The first file of a cut list starts at the first data row, wherever the table begins.
+pub fn parse_row_ranges(text: &str) -> Result<Vec<RowRange>> {
+    let entries: Vec<&str> = text
+        .split(|ch: char| matches!(ch, ',' | '，' | ';' | '；' | '、') || ch.is_whitespace())
+        .filter(|entry| !entry.is_empty())
+        .collect();
+    if entries.is_empty() {
+        return Err(anyhow!("请至少填写一个行范围或拆分点"));
+    }
+
+    let is_range = |entry: &str| entry.contains(['-', '~', '～']);
+    if entries.iter().all(|entry| is_range(entry)) {
+        entries
+            .iter()
+            .map(|entry| parse_range_entry(entry))
+            .collect()
+    } else if entries.iter().any(|entry| is_range(entry)) {
+        Err(anyhow!("行范围（如 2-120）和拆分点（如 121）不能混用"))
+    } else {
+        let mut cuts = entries
+            .iter()
+            .map(|entry| parse_row_number(entry))
+            .collect::<Result<Vec<_>>>()?;
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        let mut ranges = Vec::with_capacity(cuts.len() + 1);
+        let mut start = None;
+        for cut in cuts {
+            ranges.push(RowRange {
+                start,
+                end: Some(cut - 1),
+            });
+            start = Some(cut);
+        }
+        ranges.push(RowRange { start, end: None });
+        Ok(ranges)
+    }
+}
+
+fn parse_range_entry(entry: &str) -> Result<RowRange> {
+    let (start, end) = entry
+        .split_once(['-', '~', '～'])
+        .ok_or_else(|| anyhow!("无法识别的行范围“{entry}”"))?;
+    let start = parse_row_number(start).map_err(|_| anyhow!("无法识别的行范围“{entry}”"))?;
+    let end = if end.trim().is_empty() {
+        None
+    } else {
+        let end = parse_row_number(end).map_err(|_| anyhow!("无法识别的行范围“{entry}”"))?;
+        if end < start {
+            return Err(anyhow!("行范围“{entry}”的结束行小于起始行"));
+        }
+        Some(end)
+    };
+    Ok(RowRange {
+        start: Some(start),
+        end,
+    })
+}
+
+fn parse_row_number(text: &str) -> Result<usize> {
+    let value: usize = text
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("无法识别的行号“{}”", text.trim()))?;
+    if value == 0 {
+        return Err(anyhow!("行号从 1 开始"));
+    }
+    Ok(value)
+}
+
+/// Converts sheet row ranges into data row indices, rejecting ranges that overlap, reach into
+/// the header or the rows above the table, or start after the last row. The table's first row
+/// is sheet row `origin_row` (0-based). Data rows that no range covers are reported in
+/// `warnings`.
+pub fn plan_row_ranges(
+    ranges: &[RowRange],
+    header_rows: usize,
+    origin_row: usize,
+    data_len: usize,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Range<usize>>> {
+    let first_data_row = sheet_row_number(origin_row, header_rows);
+    let last_row = first_data_row + data_len - 1;
+    let mut resolved = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let start = range.start.unwrap_or(first_data_row);
+        if start < first_data_row {
+            return Err(anyhow!(
+                "行范围 {} 包含了表头或表格上方的行，数据从第 {first_data_row} 行开始",
+                describe(range)
+            ));
+        }
+        if start > last_row {
+            return Err(anyhow!(
+                "行范围 {} 超出了工作表的最后一行 {last_row}",
+                describe(range)
+            ));
+        }
+        let end = match range.end {
+            Some(end) if end < start => {
+                return Err(anyhow!(
+                    "拆分点 {} 必须大于第一行数据的行号 {first_data_row}",
+                    end + 1
+                ));
+            }
+            Some(end) if end > last_row => {
+                warnings.push(format!(
+                    "行范围 {} 超出了最后一行，已截止到第 {last_row} 行",
+                    describe(range)
+                ));
+                last_row
+            }
+            Some(end) => end,
+            None => last_row,
+        };
+        resolved.push((start, end));
+    }
+
+    let mut sorted: Vec<(usize, usize)> = resolved.clone();
+    sorted.sort_unstable();
+    let mut next_uncovered = first_data_row;
+    for pair in sorted.windows(2) {
+        let ((a_start, a_end), (b_start, b_end)) = (pair[0], pair[1]);
+        if b_start <= a_end {
+            return Err(anyhow!(
+                "行范围 {a_start}-{a_end} 与 {b_start}-{b_end} 重叠"
+            ));
+        }
+    }
+    for &(start, end) in &sorted {
+        if start > next_uncovered {
+            warnings.push(format!(
+                "第 {next_uncovered}-{} 行不在任何行范围内，未写入输出文件",
+                start - 1
+            ));
+        }
+        next_uncovered = end + 1;
+    }
+    if next_uncovered <= last_row {
+        warnings.push(format!(
+            "第 {next_uncovered}-{last_row} 行不在任何行范围内，未写入输出文件"
+        ));
+    }
+
+    Ok(resolved
+        .into_iter()
+        .map(|(start, end)| (start - first_data_row)..(end + 1 - first_data_row))
+        .collect())
+}
+
+fn describe(range: &RowRange) -> String {
+    let start = range
+        .start
+        .map(|start| start.to_string())
+        .unwrap_or_default();
+    match range.end {
+        Some(end) => format!("{start}-{end}"),
+        None => format!("{start}-"),
+    }
+}
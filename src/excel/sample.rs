@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use super::ChunkPlan;
+use super::collation::Collation;
+
+/// Size of one sample output.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SampleSize {
+    /// Share of the rows (of each stratum), between 0 and 1.
+    Share(f64),
+    /// Fixed number of rows (of each stratum).
+    Rows(usize),
+}
+
+impl SampleSize {
+    /// `80%` or `5行`, also used in the output file name.
+    pub fn describe(self) -> String {
+        match self {
+            SampleSize::Share(share) => {
+                let percent = format!("{:.2}", share * 100.0);
+                format!("{}%", percent.trim_end_matches('0').trim_end_matches('.'))
+            }
+            SampleSize::Rows(rows) => format!("{rows}行"),
+        }
+    }
+}
+
+/// Parses `10%`, `80%, 20%`, ratios such as `80/20` or `1/1/1` (shares of all rows), or row
+/// counts such as `5` or `100, 50`.
+pub fn parse_sample_parts(text: &str) -> Result<Vec<SampleSize>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(anyhow!("请填写抽样比例或行数，例如 10%、80/20 或 5"));
+    }
+
+    let parse_number = |item: &str| -> Result<f64> {
+        item.trim()
+            .trim_end_matches(['%', '％'])
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite() && *value > 0.0)
+            .ok_or_else(|| anyhow!("无法识别的抽样大小“{}”", item.trim()))
+    };
+
+    if text.contains('/') {
+        let weights = text
+            .split('/')
+            .map(parse_number)
+            .collect::<Result<Vec<_>>>()?;
+        let total: f64 = weights.iter().sum();
+        return Ok(weights
+            .into_iter()
+            .map(|weight| SampleSize::Share(weight / total))
+            .collect());
+    }
+
+    let items: Vec<&str> = text
+        .split([',', '，', ';', '；', '、'])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect();
+    let is_percent = |item: &&str| item.ends_with(['%', '％']);
+    if items.iter().all(is_percent) {
+        let parts = items
+            .iter()
+            .map(|item| Ok(SampleSize::Share(parse_number(item)? / 100.0)))
+            .collect::<Result<Vec<_>>>()?;
+        let total: f64 = parts
+            .iter()
+            .map(|part| match part {
+                SampleSize::Share(share) => *share,
+                SampleSize::Rows(_) => 0.0,
+            })
+            .sum();
+        if total > 1.0 + 1e-9 {
+            return Err(anyhow!("抽样比例之和不能超过 100%"));
+        }
+        Ok(parts)
+    } else if items.iter().any(is_percent) {
+        Err(anyhow!("抽样比例（如 10%）和行数（如 5）不能混用"))
+    } else {
+        items
+            .iter()
+            .map(|item| match item.parse::<usize>() {
+                Ok(0) | Err(_) => Err(anyhow!("无法识别的抽样行数“{item}”")),
+                Ok(rows) => Ok(SampleSize::Rows(rows)),
+            })
+            .collect()
+    }
+}
+
+/// SplitMix64: small, fast and, unlike library generators, fixed forever, so a seed always
+/// reproduces the same sample.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..bound`.
+    fn below(&mut self, bound: usize) -> usize {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+}
+
+/// Draws the sample outputs from rows `0..len` without replacement: the rows are shuffled with
+/// `seed` and every part takes the next rows of each stratum (all rows form one stratum when
+/// `strata` is `None`). With `shuffle` the outputs keep the shuffled order, otherwise the
+/// incoming order. Strata are taken and reported in `collation` order of their values.
+pub fn plan_samples(
+    len: usize,
+    strata: Option<&[&str]>,
+    parts: &[SampleSize],
+    seed: u64,
+    shuffle: bool,
+    collation: Collation,
+    warnings: &mut Vec<String>,
+) -> Vec<ChunkPlan> {
+    let mut permutation: Vec<usize> = (0..len).collect();
+    let mut rng = SplitMix64(seed);
+    for idx in (1..len).rev() {
+        let other = rng.below(idx + 1);
+        permutation.swap(idx, other);
+    }
+
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    let mut group_index: HashMap<&str, usize> = HashMap::new();
+    for &pos in &permutation {
+        let key = strata.map_or("", |strata| strata[pos]);
+        let idx = *group_index.entry(key).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+        groups[idx].1.push(pos);
+    }
+    groups.sort_by(|(a, _), (b, _)| collation.compare(a, b));
+
+    let mut selected: Vec<Vec<usize>> = vec![Vec::new(); parts.len()];
+    // Strata that ran out of rows before a part was full, per part.
+    let mut short: Vec<Vec<&str>> = vec![Vec::new(); parts.len()];
+    for (key, rows) in &groups {
+        let mut remaining = rows.as_slice();
+        for (idx, count) in part_counts(rows.len(), parts).into_iter().enumerate() {
+            if count > remaining.len() {
+                short[idx].push(key);
+            }
+            let (taken, rest) = remaining.split_at(count.min(remaining.len()));
+            selected[idx].extend_from_slice(taken);
+            remaining = rest;
+        }
+    }
+    for (idx, keys) in short.iter().enumerate() {
+        let Some(first) = keys.first() else {
+            continue;
+        };
+        let describe = parts[idx].describe();
+        warnings.push(match strata {
+            Some(_) => format!(
+                "样本{} 有 {} 个分组（如“{first}”）剩余行数不足 {describe}，已抽取全部剩余行",
+                idx + 1,
+                keys.len()
+            ),
+            None => format!("样本{} 剩余行数不足 {describe}，已抽取全部剩余行", idx + 1),
+        });
+    }
+
+    let mut rank = vec![0; len];
+    for (idx, &pos) in permutation.iter().enumerate() {
+        rank[pos] = idx;
+    }
+    selected
+        .into_iter()
+        .zip(parts)
+        .enumerate()
+        .map(|(idx, (mut rows, part))| {
+            if shuffle {
+                rows.sort_unstable_by_key(|&pos| rank[pos]);
+            } else {
+                rows.sort_unstable();
+            }
+            ChunkPlan {
+                rows,
+                group_key: Some(format!("样本{}_{}", idx + 1, part.describe())),
+                columns: None,
+            }
+        })
+        .collect()
+}
+
+/// Rows each part takes from a stratum of `len` rows. Shares are rounded by largest
+/// remainder, so `80/20` always covers the whole stratum.
+fn part_counts(len: usize, parts: &[SampleSize]) -> Vec<usize> {
+    let exact: Vec<f64> = parts
+        .iter()
+        .map(|part| match part {
+            SampleSize::Share(share) => len as f64 * share,
+            SampleSize::Rows(rows) => *rows as f64,
+        })
+        .collect();
+    let mut counts: Vec<usize> = exact.iter().map(|value| value.floor() as usize).collect();
+    let target = exact.iter().sum::<f64>().round() as usize;
+    let mut by_remainder: Vec<usize> = (0..parts.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        let fraction = |idx: usize| exact[idx] - exact[idx].floor();
+        fraction(b).total_cmp(&fraction(a)).then(a.cmp(&b))
+    });
+    let missing = target.saturating_sub(counts.iter().sum());
+    for &idx in by_remainder.iter().take(missing) {
+        counts[idx] += 1;
+    }
+    counts
+}
@@ -0,0 +1,94 @@
+use std::ops::Range;
+
+use super::{ChunkPlan, sheet_row_number};
+
+/// Cuts the rows into sections at blank rows and at rows whose first non-empty cell starts
+/// with one of `markers` (such as `小计` or `合计`). Blank rows are dropped; a marker row is
+/// kept as the last row of the section it closes.
+fn plan_sections(rows: &[&[String]], markers: &[String]) -> Vec<Range<usize>> {
+    let mut sections = Vec::new();
+    let mut start = None;
+
+    for (idx, row) in rows.iter().enumerate() {
+        if is_blank(row) {
+            if let Some(start) = start.take() {
+                sections.push(start..idx);
+            }
+            continue;
+        }
+
+        let section_start = *start.get_or_insert(idx);
+        if is_marker(row, markers) {
+            sections.push(section_start..idx + 1);
+            start = None;
+        }
+    }
+
+    if let Some(start) = start {
+        sections.push(start..rows.len());
+    }
+    sections
+}
+
+/// One output per section of the sheet's data rows. Sections are found in sheet order, so
+/// separators stay where they are; each section then takes the rows of `order` (the data rows
+/// left after filtering, in output order) that lie in it, and sections left without rows are
+/// dropped. Sections holding no more than their `section_header_rows` are reported with their
+/// sheet rows, counting the `header_rows` above the data and the `origin_row` rows above the
+/// table.
+pub fn plan_section_parts(
+    data_rows: &[Vec<String>],
+    order: &[usize],
+    markers: &[String],
+    section_header_rows: usize,
+    header_rows: usize,
+    origin_row: usize,
+    warnings: &mut Vec<String>,
+) -> Vec<ChunkPlan> {
+    let rows: Vec<&[String]> = data_rows.iter().map(Vec::as_slice).collect();
+    let mut positions = vec![None; data_rows.len()];
+    for (pos, &idx) in order.iter().enumerate() {
+        positions[idx] = Some(pos);
+    }
+
+    let mut plans = Vec::new();
+    for section in plan_sections(&rows, markers) {
+        let mut rows: Vec<usize> = section.clone().filter_map(|idx| positions[idx]).collect();
+        if rows.is_empty() {
+            continue;
+        }
+        rows.sort_unstable();
+        if section.len() <= section_header_rows {
+            warnings.push(format!(
+                "第 {}-{} 行的分段只有表头，没有数据",
+                sheet_row_number(origin_row, header_rows + section.start),
+                sheet_row_number(origin_row, header_rows + section.end - 1)
+            ));
+        }
+        plans.push(ChunkPlan {
+            rows,
+            group_key: None,
+            columns: None,
+        });
+    }
+    if plans.is_empty() {
+        plans.push(ChunkPlan::from(0..0));
+    }
+    plans
+}
+
+fn is_blank(row: &[String]) -> bool {
+    row.iter().all(|value| value.trim().is_empty())
+}
+
+fn is_marker(row: &[String], markers: &[String]) -> bool {
+    let Some(first) = row.iter().find(|value| !value.trim().is_empty()) else {
+        return false;
+    };
+    // Reports often pad labels, e.g. `合  计`.
+    let first: String = first.chars().filter(|ch| !ch.is_whitespace()).collect();
+    markers
+        .iter()
+        .map(|marker| marker.trim())
+        .any(|marker| !marker.is_empty() && first.starts_with(marker))
+}
@@ -0,0 +1,184 @@
+use std::cmp::Ordering;
+
+use anyhow::{Result, anyhow};
+use calamine::Data;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::collation::Collation;
+use super::period::parse_text_date;
+use super::{MergeRange, resolve_column};
+
+/// One level of a multi-key sort.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortKey {
+    /// Header text or column letter.
+    pub column: String,
+    pub descending: bool,
+}
+
+/// Parses keys such as `部门, 金额 降序, C desc`. Keys are separated by commas or semicolons;
+/// a trailing `asc`/`desc`/`升序`/`降序` sets the direction, ascending otherwise.
+pub fn parse_sort_keys(text: &str) -> Result<Vec<SortKey>> {
+    text.split([',', '，', ';', '；', '、'])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (column, descending) = match item.rsplit_once(char::is_whitespace) {
+                Some((column, direction)) => match direction.to_lowercase().as_str() {
+                    "asc" | "升序" | "↑" => (column.trim(), false),
+                    "desc" | "降序" | "↓" => (column.trim(), true),
+                    _ => (item, false),
+                },
+                None => (item, false),
+            };
+            if column.is_empty() {
+                return Err(anyhow!("排序条件“{item}”缺少列名"));
+            }
+            Ok(SortKey {
+                column: column.to_string(),
+                descending,
+            })
+        })
+        .collect()
+}
+
+/// A cell reduced to what decides its place in the order.
+#[derive(Debug, PartialEq)]
+enum SortValue {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error,
+    Blank,
+}
+
+impl SortValue {
+    /// Numbers and dates (as Excel serial numbers, so real dates and date text mix) come
+    /// first, then text, booleans and errors, like Excel's own sort.
+    fn from_cell(cell: &Data) -> SortValue {
+        match cell {
+            Data::Int(i) => SortValue::Number(*i as f64),
+            Data::Float(f) => SortValue::Number(*f),
+            Data::DateTime(dt) => SortValue::Number(dt.as_f64()),
+            Data::Bool(b) => SortValue::Bool(*b),
+            Data::Error(_) => SortValue::Error,
+            Data::Empty => SortValue::Blank,
+            Data::String(text) | Data::DateTimeIso(text) | Data::DurationIso(text) => {
+                let text = text.trim();
+                if text.is_empty() {
+                    SortValue::Blank
+                } else if let Some(number) =
+                    text.parse::<f64>().ok().filter(|number| number.is_finite())
+                {
+                    // "inf" and "NaN" parse as floats but are words in a sheet.
+                    SortValue::Number(number)
+                } else if let Some(date) = parse_text_date(text) {
+                    SortValue::Number(excel_serial(date))
+                } else {
+                    SortValue::Text(text.to_string())
+                }
+            }
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            SortValue::Number(_) => 0,
+            SortValue::Text(_) => 1,
+            SortValue::Bool(_) => 2,
+            SortValue::Error => 3,
+            SortValue::Blank => 4,
+        }
+    }
+
+    fn compare(&self, other: &SortValue, collation: Collation) -> Ordering {
+        match (self, other) {
+            (SortValue::Number(a), SortValue::Number(b)) => a.total_cmp(b),
+            (SortValue::Text(a), SortValue::Text(b)) => collation.compare(a, b),
+            (SortValue::Bool(a), SortValue::Bool(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+fn excel_serial(date: NaiveDate) -> f64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).expect("valid epoch");
+    (date - epoch).num_days() as f64
+}
+
+/// Reorders `order` (indices into `cells`) by `keys`. The sort is stable, so rows with equal
+/// keys keep their sheet order; blank cells go last in either direction. Text is ordered by
+/// `collation`.
+pub fn sort_rows(
+    order: &mut [usize],
+    keys: &[SortKey],
+    collation: Collation,
+    header: &[Vec<String>],
+    first_column: usize,
+    cells: &[Vec<Data>],
+) -> Result<()> {
+    let columns = keys
+        .iter()
+        .map(|key| {
+            Ok((
+                resolve_column(&key.column, header, first_column)?,
+                key.descending,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let values: Vec<Vec<SortValue>> = cells
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|&(col, _)| SortValue::from_cell(row.get(col).unwrap_or(&Data::Empty)))
+                .collect()
+        })
+        .collect();
+
+    order.sort_by(|&a, &b| {
+        columns
+            .iter()
+            .enumerate()
+            .map(|(level, &(_, descending))| {
+                let (left, right) = (&values[a][level], &values[b][level]);
+                match (left, right) {
+                    (SortValue::Blank, _) | (_, SortValue::Blank) => left.compare(right, collation),
+                    _ if descending => right.compare(left, collation),
+                    _ => left.compare(right, collation),
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(())
+}
+
+/// Counts merged ranges over several data rows whose rows are no longer adjacent in `order`;
+/// those merges cannot be carried into the outputs.
+pub fn broken_merges(merges: &[MergeRange], header_rows: usize, order: &[usize]) -> usize {
+    let mut positions = vec![None; order.iter().max().map_or(0, |max| max + 1)];
+    for (pos, &idx) in order.iter().enumerate() {
+        positions[idx] = Some(pos);
+    }
+    merges
+        .iter()
+        .filter(|merge| merge.start_row >= header_rows && merge.end_row > merge.start_row)
+        .filter(|merge| {
+            let start = positions
+                .get(merge.start_row - header_rows)
+                .copied()
+                .flatten();
+            let Some(start) = start else {
+                return false;
+            };
+            !(merge.start_row..=merge.end_row)
+                .enumerate()
+                .all(|(offset, row)| {
+                    positions.get(row - header_rows).copied().flatten() == Some(start + offset)
+                })
+        })
+        .count()
+}
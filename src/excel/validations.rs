@@ -0,0 +1,469 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use anyhow::Result;
+use calamine::{Reader, Sheets, open_workbook_auto};
+use quick_xml::{Reader as XmlReader, events::Event};
+use rust_xlsxwriter::{DataValidation, DataValidationErrorStyle, DataValidationRule, Formula};
+
+use super::formats::DATE1904_OFFSET;
+use super::{
+    ChunkPositions, LAST_SHEET_ROW, MergeRange, OutputRange, RuleAnchor, RuleRange,
+    column_label_to_index, format_cell, open_xlsx_archive, parse_range_ref, parse_sqref,
+    read_sheet_xml, refers_elsewhere, uses_1904_dates,
+};
+
+/// Excel's limits for the titles and messages of a validation.
+const MAX_TITLE_CHARS: usize = 32;
+const MAX_MESSAGE_CHARS: usize = 255;
+
+/// A validation rule and the cells it applies to.
+pub struct Validation {
+    /// The rule without its condition when it has one.
+    rule: DataValidation,
+    /// Kept as text, so every output gets its references moved along with its cells.
+    condition: Option<Condition>,
+    sqref: String,
+    anchor: RuleAnchor,
+    ranges: Vec<RuleRange>,
+}
+
+/// The condition of a validation other than a drop-down list.
+struct Condition {
+    kind: String,
+    operator: String,
+    first: Option<String>,
+    second: Option<String>,
+}
+
+/// A validation rule at its ranges in one output file.
+pub struct ChunkValidation {
+    pub rule: DataValidation,
+    pub ranges: Vec<OutputRange>,
+}
+
+/// Reads the data validations of `sheet_name`, including those newer Excel versions keep in
+/// the sheet's extension list. Positions are relative to `origin`, the sheet row and column of
+/// the first cell calamine returned; `total_rows` is the number of rows read. Drop-down lists
+/// taken from a range or a defined name are replaced by the values they show, since the
+/// outputs only carry one sheet. Rules whose conditions refer to other sheets or to names are
+/// dropped with a warning; date limits of 1904-based workbooks are moved to the 1900 date
+/// system.
+pub fn extract_validations(
+    source: &Path,
+    sheet_name: &str,
+    origin: (usize, usize),
+    total_rows: usize,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Validation>> {
+    let Some(mut archive) = open_xlsx_archive(source)? else {
+        return Ok(Vec::new());
+    };
+    let (_, sheet_xml) = read_sheet_xml(&mut archive, sheet_name)?;
+    let entries = parse_validations(&sheet_xml);
+    if entries.is_empty() || total_rows == 0 {
+        return Ok(Vec::new());
+    }
+    let date1904 = uses_1904_dates(&mut archive);
+    let mut workbook = open_workbook_auto(source)?;
+    let names = workbook.defined_names().to_vec();
+    let context = RuleContext {
+        sheet_name,
+        names: &names,
+        date1904,
+    };
+
+    let mut validations = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in entries {
+        let ranges = parse_sqref(&entry.sqref, origin, total_rows);
+        let Some(anchor) = RuleAnchor::new(&entry.sqref, origin) else {
+            continue;
+        };
+        if ranges.is_empty() {
+            continue;
+        }
+        match build_rule(&entry, &mut workbook, &context) {
+            Ok((rule, condition)) => validations.push(Validation {
+                rule,
+                condition,
+                sqref: entry.sqref.trim().to_string(),
+                anchor,
+                ranges,
+            }),
+            Err(reason) => skipped.push(format!("{} {reason}", entry.sqref.trim())),
+        }
+    }
+    if let Some(example) = skipped.first() {
+        warnings.push(format!(
+            "有 {} 条数据验证规则无法在输出文件中保留（如 {example}），相应单元格不再限制输入",
+            skipped.len()
+        ));
+    }
+    Ok(validations)
+}
+
+/// The validations of one output file, with their ranges and the references of their
+/// conditions moved along with their cells. Rules whose conditions refer to cells the file
+/// does not hold are left out and described in `dropped`.
+pub fn map_chunk_validations(
+    validations: &[Validation],
+    positions: &ChunkPositions,
+    dropped: &mut Vec<String>,
+) -> Vec<ChunkValidation> {
+    validations
+        .iter()
+        .filter_map(|validation| {
+            let ranges: Vec<OutputRange> = validation
+                .ranges
+                .iter()
+                .flat_map(|range| positions.rule_ranges(range))
+                .collect();
+            if ranges.is_empty() {
+                return None;
+            }
+            let Some(condition) = &validation.condition else {
+                return Some(ChunkValidation {
+                    rule: validation.rule.clone(),
+                    ranges,
+                });
+            };
+            let moved = |formula: &Option<String>| match formula {
+                Some(formula) => positions
+                    .rule_formula(formula, validation.anchor, &validation.ranges, &ranges)
+                    .map(Some),
+                None => Some(None),
+            };
+            let rule = moved(&condition.first)
+                .zip(moved(&condition.second))
+                .and_then(|(first, second)| {
+                    condition
+                        .apply(validation.rule.clone(), first.as_deref(), second.as_deref())
+                        .ok()
+                });
+            match rule {
+                Some(rule) => Some(ChunkValidation { rule, ranges }),
+                None => {
+                    dropped.push(format!("数据验证 {}", validation.sqref));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// One `<dataValidation>` element of the sheet XML.
+#[derive(Default)]
+struct ValidationEntry {
+    kind: String,
+    operator: String,
+    error_style: String,
+    allow_blank: bool,
+    hide_dropdown: bool,
+    show_input: bool,
+    show_error: bool,
+    prompt_title: Option<String>,
+    prompt: Option<String>,
+    error_title: Option<String>,
+    error: Option<String>,
+    formula1: Option<String>,
+    formula2: Option<String>,
+    sqref: String,
+}
+
+/// The child element whose text is being read.
+#[derive(Clone, Copy)]
+enum EntryField {
+    Formula1,
+    Formula2,
+    Sqref,
+}
+
+fn parse_validations(sheet_xml: &str) -> Vec<ValidationEntry> {
+    let mut reader = XmlReader::from_str(sheet_xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut entries = Vec::new();
+    let mut current: Option<ValidationEntry> = None;
+    let mut field = None;
+    while let Ok(event) = reader.read_event_into(&mut buf) {
+        match event {
+            Event::Eof => break,
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"dataValidation" => current = Some(read_entry(&e, &reader)),
+                b"formula1" => field = Some(EntryField::Formula1),
+                b"formula2" => field = Some(EntryField::Formula2),
+                // Validations in the extension list name their cells in an `<xm:sqref>`.
+                b"sqref" => field = Some(EntryField::Sqref),
+                _ => {}
+            },
+            Event::Empty(e) if e.local_name().as_ref() == b"dataValidation" => {
+                entries.push(read_entry(&e, &reader));
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"formula1" | b"formula2" | b"sqref" => field = None,
+                b"dataValidation" => entries.extend(current.take()),
+                _ => {}
+            },
+            Event::Text(text) => {
+                let (Some(entry), Some(field), Ok(text)) =
+                    (current.as_mut(), field, text.unescape())
+                else {
+                    continue;
+                };
+                let target = match field {
+                    EntryField::Formula1 => entry.formula1.get_or_insert_with(String::new),
+                    EntryField::Formula2 => entry.formula2.get_or_insert_with(String::new),
+                    EntryField::Sqref => &mut entry.sqref,
+                };
+                target.push_str(&text);
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    entries
+}
+
+fn read_entry(e: &quick_xml::events::BytesStart, reader: &XmlReader<&[u8]>) -> ValidationEntry {
+    let mut entry = ValidationEntry::default();
+    for attr in e.attributes().flatten() {
+        let Ok(value) = attr.decode_and_unescape_value(reader) else {
+            continue;
+        };
+        let value = value.into_owned();
+        let flag = value == "1" || value == "true";
+        match attr.key.as_ref() {
+            b"type" => entry.kind = value,
+            b"operator" => entry.operator = value,
+            b"errorStyle" => entry.error_style = value,
+            b"allowBlank" => entry.allow_blank = flag,
+            // Despite its name, the attribute hides the drop-down arrow.
+            b"showDropDown" => entry.hide_dropdown = flag,
+            b"showInputMessage" => entry.show_input = flag,
+            b"showErrorMessage" => entry.show_error = flag,
+            b"promptTitle" => entry.prompt_title = Some(value),
+            b"prompt" => entry.prompt = Some(value),
+            b"errorTitle" => entry.error_title = Some(value),
+            b"error" => entry.error = Some(value),
+            b"sqref" => entry.sqref = value,
+            _ => {}
+        }
+    }
+    entry
+}
+
+/// What [`build_rule`] needs to know about the source sheet.
+struct RuleContext<'a> {
+    sheet_name: &'a str,
+    names: &'a [(String, String)],
+    date1904: bool,
+}
+
+/// The rule of one validation and its condition, or why it cannot be written. A drop-down
+/// list is part of the rule; any other condition is checked here and returned on its own.
+fn build_rule(
+    entry: &ValidationEntry,
+    workbook: &mut Sheets<BufReader<File>>,
+    context: &RuleContext,
+) -> Result<(DataValidation, Option<Condition>), String> {
+    let (sheet_name, names) = (context.sheet_name, context.names);
+    let rebase = |formula: &str| -> String {
+        // Only plain serial numbers are rebased; `DATE(...)` means the same in both systems.
+        match formula.parse::<f64>() {
+            Ok(serial) if context.date1904 && entry.kind == "date" && serial.is_finite() => {
+                (serial + DATE1904_OFFSET).to_string()
+            }
+            _ => formula.to_string(),
+        }
+    };
+    let first = entry.formula1.as_deref().map(str::trim).map(rebase);
+    let second = entry.formula2.as_deref().map(str::trim).map(rebase);
+    let mut rule = DataValidation::new();
+    let mut condition = None;
+
+    if entry.kind == "list" {
+        let list = first.as_deref().ok_or("的下拉列表为空")?;
+        if list.starts_with('"') {
+            rule = rule.allow_list_formula(Formula::new(list));
+        } else {
+            let values = list_values(workbook, sheet_name, names, list)
+                .filter(|values| !values.is_empty())
+                .ok_or_else(|| format!("的下拉列表来自无法读取的区域“{list}”"))?;
+            if values.iter().any(|value| value.contains(',')) {
+                return Err(format!("的下拉列表“{list}”中有含逗号的选项"));
+            }
+            rule = rule
+                .allow_list_strings(&values)
+                .map_err(|_| format!("的下拉列表“{list}”超过 255 个字符"))?;
+        }
+    } else {
+        for formula in first.iter().chain(second.iter()) {
+            if refers_elsewhere(formula, names) {
+                return Err(format!("的条件“{formula}”引用了其他工作表或名称"));
+            }
+        }
+        let entry_condition = Condition {
+            kind: entry.kind.clone(),
+            operator: entry.operator.clone(),
+            first,
+            second,
+        };
+        entry_condition.apply(
+            DataValidation::new(),
+            entry_condition.first.as_deref(),
+            entry_condition.second.as_deref(),
+        )?;
+        condition = Some(entry_condition);
+    }
+
+    rule = rule
+        .ignore_blank(entry.allow_blank)
+        .show_dropdown(!entry.hide_dropdown)
+        .show_input_message(entry.show_input)
+        .show_error_message(entry.show_error)
+        .set_error_style(match entry.error_style.as_str() {
+            "warning" => DataValidationErrorStyle::Warning,
+            "information" => DataValidationErrorStyle::Information,
+            _ => DataValidationErrorStyle::Stop,
+        });
+    // Texts over Excel's limits are cut, so the calls below cannot fail.
+    if let Some(title) = &entry.prompt_title
+        && let Ok(with_title) = rule
+            .clone()
+            .set_input_title(truncate(title, MAX_TITLE_CHARS))
+    {
+        rule = with_title;
+    }
+    if let Some(prompt) = &entry.prompt
+        && let Ok(with_prompt) = rule
+            .clone()
+            .set_input_message(truncate(prompt, MAX_MESSAGE_CHARS))
+    {
+        rule = with_prompt;
+    }
+    if let Some(title) = &entry.error_title
+        && let Ok(with_title) = rule
+            .clone()
+            .set_error_title(truncate(title, MAX_TITLE_CHARS))
+    {
+        rule = with_title;
+    }
+    if let Some(error) = &entry.error
+        && let Ok(with_error) = rule
+            .clone()
+            .set_error_message(truncate(error, MAX_MESSAGE_CHARS))
+    {
+        rule = with_error;
+    }
+    Ok((rule, condition))
+}
+
+impl Condition {
+    /// `rule` limited by this condition, with `first` and `second` for its formulas.
+    fn apply(
+        &self,
+        rule: DataValidation,
+        first: Option<&str>,
+        second: Option<&str>,
+    ) -> Result<DataValidation, String> {
+        let condition = || {
+            comparison(
+                &self.operator,
+                first.map(Formula::new),
+                second.map(Formula::new),
+            )
+            .ok_or("的条件不完整")
+        };
+        Ok(match self.kind.as_str() {
+            "" | "none" => rule.allow_any_value(),
+            "whole" => rule.allow_whole_number_formula(condition()?),
+            "decimal" => rule.allow_decimal_number_formula(condition()?),
+            "date" => rule.allow_date_formula(condition()?),
+            "time" => rule.allow_time_formula(condition()?),
+            "textLength" => rule.allow_text_length_formula(condition()?),
+            "custom" => rule.allow_custom(Formula::new(first.ok_or("的公式为空")?)),
+            other => return Err(format!("的类型“{other}”无法识别")),
+        })
+    }
+}
+
+/// The comparison of an `operator` attribute; Excel leaves out the default, `between`.
+fn comparison(
+    operator: &str,
+    first: Option<Formula>,
+    second: Option<Formula>,
+) -> Option<DataValidationRule<Formula>> {
+    let first = first?;
+    Some(match operator {
+        "equal" => DataValidationRule::EqualTo(first),
+        "notEqual" => DataValidationRule::NotEqualTo(first),
+        "greaterThan" => DataValidationRule::GreaterThan(first),
+        "greaterThanOrEqual" => DataValidationRule::GreaterThanOrEqualTo(first),
+        "lessThan" => DataValidationRule::LessThan(first),
+        "lessThanOrEqual" => DataValidationRule::LessThanOrEqualTo(first),
+        "notBetween" => DataValidationRule::NotBetween(first, second?),
+        _ => DataValidationRule::Between(first, second?),
+    })
+}
+
+/// The values shown in the cells a drop-down list refers to, by range or defined name.
+fn list_values(
+    workbook: &mut Sheets<BufReader<File>>,
+    sheet_name: &str,
+    names: &[(String, String)],
+    list: &str,
+) -> Option<Vec<String>> {
+    let mut reference = list.trim_start_matches('=').trim();
+    if let Some((_, target)) = names
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(reference))
+    {
+        reference = target.trim_start_matches('=').trim();
+    }
+    let (sheet, cells) = match reference.rsplit_once('!') {
+        Some((sheet, cells)) => {
+            let sheet = sheet
+                .strip_prefix('\'')
+                .and_then(|sheet| sheet.strip_suffix('\''))
+                .map_or_else(|| sheet.to_string(), |sheet| sheet.replace("''", "'"));
+            (sheet, cells)
+        }
+        None => (sheet_name.to_string(), reference),
+    };
+    let range = parse_range_ref(cells).or_else(|| column_range(cells))?;
+    let values = workbook.worksheet_range(&sheet).ok()?;
+    let (start, end) = (values.start()?, values.end()?);
+
+    let mut list = Vec::new();
+    for row in range.start_row.max(start.0 as usize)..=range.end_row.min(end.0 as usize) {
+        for col in range.start_col.max(start.1 as usize)..=range.end_col.min(end.1 as usize) {
+            let Some(value) = values.get_value((row as u32, col as u32)) else {
+                continue;
+            };
+            let text = format_cell(value);
+            if !text.trim().is_empty() {
+                list.push(text);
+            }
+        }
+    }
+    Some(list)
+}
+
+/// A range of whole columns such as `$A:$A`.
+fn column_range(cells: &str) -> Option<MergeRange> {
+    let (start, end) = cells.split_once(':')?;
+    let start = column_label_to_index(start.trim().trim_start_matches('$'))?;
+    let end = column_label_to_index(end.trim().trim_start_matches('$'))?;
+    Some(MergeRange {
+        start_row: 0,
+        end_row: LAST_SHEET_ROW,
+        start_col: start.min(end),
+        end_col: start.max(end),
+    })
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
@@ -0,0 +1,376 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+
+use super::manifest::{build_manifest_path, read_manifest, sha256_file};
+use super::{
+    ChunkContext, ChunkPlan, SheetExtras, SplitMode, build_output_path, cell_ref,
+    extract_merge_ranges, map_chunk_merges, read_first_sheet_cells, select_rows, sheet_row_number,
+};
+
+/// At most this many cell differences are kept in the report; the rest are only counted.
+const MAX_REPORTED_DIFFERENCES: usize = 20;
+
+/// Outcome of comparing a split set against its source sheet.
+pub struct VerifyReport {
+    pub files_checked: Vec<PathBuf>,
+    pub source_data_rows: usize,
+    /// Data rows the outputs should hold together: the source data rows the recorded options
+    /// keep, counted once per file that holds them.
+    pub expected_data_rows: usize,
+    pub output_data_rows: usize,
+    /// `None` when no manifest was used, otherwise whether the source still matches its recorded hash.
+    pub source_hash_matches: Option<bool>,
+    /// Output files whose content no longer matches the SHA-256 recorded in the manifest.
+    pub hash_mismatches: Vec<PathBuf>,
+    pub differences: Vec<CellDifference>,
+    pub total_differences: usize,
+}
+
+/// A single cell that differs between the source and the reassembled outputs.
+pub struct CellDifference {
+    pub source_cell: Option<String>,
+    pub output_file: Option<PathBuf>,
+    pub output_cell: Option<String>,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl VerifyReport {
+    pub fn is_identical(&self) -> bool {
+        self.total_differences == 0
+            && self.hash_mismatches.is_empty()
+            && self.source_hash_matches != Some(false)
+    }
+}
+
+/// Reads every output of a split and compares it cell by cell with what it should hold.
+///
+/// When a manifest exists next to the source, the options recorded in it are applied to the
+/// source again — hidden rows, filter, duplicates, sort, output columns and the split mode —
+/// so every output is compared with exactly the rows and columns it was written with.
+/// Without a manifest, `<stem>_partN.xlsx` files are read in order, their data after
+/// `header_rows` header rows is concatenated and compared with the source data as it is.
+pub fn verify_split(source: &Path, header_rows: usize) -> Result<VerifyReport> {
+    let manifest_path = build_manifest_path(source);
+    if !manifest_path.exists() {
+        return verify_concatenated(source, header_rows);
+    }
+    let manifest = read_manifest(&manifest_path)?;
+    let base_dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+
+    let options = &manifest.options;
+    let selection = select_rows(source, options)?;
+    let source_origin = selection.origin;
+    let source_data_rows = selection.data_rows.len();
+    // Merged cells show their value in the first cell, which moves with reordered columns.
+    let extras = SheetExtras {
+        merges: extract_merge_ranges(source, &selection.sheet_name, selection.origin)?,
+        ..SheetExtras::default()
+    };
+    let context = ChunkContext::new(source, options, selection, extras);
+    let header_rows = context.header.len();
+    let plans = match options.mode {
+        // Size splits cut the rows into consecutive runs whose lengths only the manifest knows.
+        SplitMode::FileSize { .. } => {
+            let mut start = 0;
+            manifest
+                .chunks
+                .iter()
+                .map(|chunk| {
+                    let end = (start + chunk.data_rows).min(context.order.len());
+                    let range = start.min(end)..end;
+                    start = end;
+                    ChunkPlan::from(range)
+                })
+                .collect()
+        }
+        _ => context.plan_parts(options, &mut Vec::new())?,
+    };
+
+    let mut report = VerifyReport {
+        files_checked: Vec::new(),
+        source_data_rows,
+        expected_data_rows: plans.iter().map(|plan| plan.rows.len()).sum(),
+        output_data_rows: 0,
+        source_hash_matches: Some(sha256_file(source)? == manifest.source.sha256),
+        hash_mismatches: Vec::new(),
+        differences: Vec::new(),
+        total_differences: 0,
+    };
+
+    for idx in 0..plans.len().max(manifest.chunks.len()) {
+        let plan = plans.get(idx);
+        let columns = plan.and_then(|plan| context.plan_columns(plan));
+        let columns = columns.as_deref();
+        let rows: Vec<usize> = plan.map_or_else(Vec::new, |plan| {
+            plan.rows.iter().map(|&pos| context.order[pos]).collect()
+        });
+        // Each expected output row with the source row it comes from.
+        let mut expected: Vec<(usize, Vec<String>)> = context
+            .output_header
+            .iter()
+            .enumerate()
+            .map(|(row, cells)| (row, project(cells, columns)))
+            .chain(
+                rows.iter()
+                    .map(|&row| (header_rows + row, project(&context.data_rows[row], columns))),
+            )
+            .collect();
+        let merges = map_chunk_merges(
+            &context.extras.merges,
+            header_rows,
+            &rows,
+            columns,
+            &context.output_header,
+            &context.data_rows,
+        );
+        for merge in merges {
+            for row in merge.start_row as usize..=merge.end_row as usize {
+                let Some((_, cells)) = expected.get_mut(row) else {
+                    continue;
+                };
+                for col in merge.start_col as usize..=merge.end_col as usize {
+                    if cells.len() <= col {
+                        cells.resize(col + 1, String::new());
+                    }
+                    cells[col] =
+                        if (row, col) == (merge.start_row as usize, merge.start_col as usize) {
+                            merge.value.clone()
+                        } else {
+                            String::new()
+                        };
+                }
+            }
+        }
+
+        let Some(chunk) = manifest.chunks.get(idx) else {
+            // A file the options call for that the manifest does not list.
+            for (source_row, cells) in &expected[header_rows..] {
+                record_missing_row(
+                    &mut report,
+                    SheetRow::new(source_origin, *source_row),
+                    cells,
+                );
+            }
+            continue;
+        };
+        let path = base_dir.join(&chunk.file);
+        if !path.exists() {
+            return Err(anyhow!("拆分结果文件不存在: {}", path.display()));
+        }
+        if sha256_file(&path)? != chunk.sha256 {
+            report.hash_mismatches.push(path.clone());
+        }
+
+        let output_sheet = read_first_sheet_cells(&path)?;
+        let (output_rows, output_origin) = (output_sheet.text_rows(), output_sheet.origin);
+        for (output_row, actual) in output_rows.iter().enumerate() {
+            let output = SheetRow::new(output_origin, output_row);
+            match expected.get(output_row) {
+                Some((source_row, cells)) => compare_rows(
+                    &mut report,
+                    Some(SheetRow {
+                        columns,
+                        ..SheetRow::new(source_origin, *source_row)
+                    }),
+                    cells,
+                    &path,
+                    output,
+                    actual,
+                ),
+                None => compare_rows(&mut report, None, &[], &path, output, actual),
+            }
+        }
+        for (source_row, cells) in expected.iter().skip(output_rows.len()) {
+            record_missing_row(
+                &mut report,
+                SheetRow::new(source_origin, *source_row),
+                cells,
+            );
+        }
+
+        report.output_data_rows += output_rows.len().saturating_sub(header_rows);
+        report.files_checked.push(path);
+    }
+
+    Ok(report)
+}
+
+/// Compares the concatenated data of `<stem>_partN.xlsx` with the source data, for splits
+/// without a manifest.
+fn verify_concatenated(source: &Path, header_rows: usize) -> Result<VerifyReport> {
+    let outputs = discover_outputs(source);
+    if outputs.is_empty() {
+        return Err(anyhow!("没有找到任何拆分结果文件"));
+    }
+
+    let source_sheet = read_first_sheet_cells(source)?;
+    let (source_rows, source_origin) = (source_sheet.text_rows(), source_sheet.origin);
+    if source_rows.len() < header_rows {
+        return Err(anyhow!("工作表的行数小于指定的表头行数"));
+    }
+    let (source_header, source_data) = source_rows.split_at(header_rows);
+
+    let mut report = VerifyReport {
+        files_checked: Vec::new(),
+        source_data_rows: source_data.len(),
+        expected_data_rows: source_data.len(),
+        output_data_rows: 0,
+        source_hash_matches: None,
+        hash_mismatches: Vec::new(),
+        differences: Vec::new(),
+        total_differences: 0,
+    };
+
+    let mut source_idx = 0;
+    for path in &outputs {
+        let output_sheet = read_first_sheet_cells(path)?;
+        let (output_rows, output_origin) = (output_sheet.text_rows(), output_sheet.origin);
+        let header_len = header_rows.min(output_rows.len());
+        for (row_idx, row) in output_rows[..header_len].iter().enumerate() {
+            compare_rows(
+                &mut report,
+                Some(SheetRow::new(source_origin, row_idx)),
+                &source_header[row_idx],
+                path,
+                SheetRow::new(output_origin, row_idx),
+                row,
+            );
+        }
+
+        for (offset, row) in output_rows[header_len..].iter().enumerate() {
+            let output = SheetRow::new(output_origin, header_len + offset);
+            match source_data.get(source_idx) {
+                Some(expected) => compare_rows(
+                    &mut report,
+                    Some(SheetRow::new(source_origin, header_rows + source_idx)),
+                    expected,
+                    path,
+                    output,
+                    row,
+                ),
+                None => compare_rows(&mut report, None, &[], path, output, row),
+            }
+            source_idx += 1;
+        }
+
+        report.output_data_rows += output_rows.len() - header_len;
+        report.files_checked.push(path.clone());
+    }
+
+    for (offset, expected) in source_data.iter().enumerate().skip(source_idx) {
+        record_missing_row(
+            &mut report,
+            SheetRow::new(source_origin, header_rows + offset),
+            expected,
+        );
+    }
+
+    Ok(report)
+}
+
+/// The cells of `row` written to an output limited to `columns`.
+fn project(row: &[String], columns: Option<&[usize]>) -> Vec<String> {
+    match columns {
+        Some(columns) => columns
+            .iter()
+            .map(|&col| row.get(col).cloned().unwrap_or_default())
+            .collect(),
+        None => row.to_vec(),
+    }
+}
+
+/// A row read for comparison, placed on its sheet to name its cells as Excel does.
+#[derive(Clone, Copy)]
+struct SheetRow<'a> {
+    /// Sheet row and column of the first cell read from the sheet.
+    origin: (usize, usize),
+    /// Row in the cells read.
+    row: usize,
+    /// Source columns of an output that does not keep them all, in output order.
+    columns: Option<&'a [usize]>,
+}
+
+impl SheetRow<'_> {
+    fn new(origin: (usize, usize), row: usize) -> Self {
+        Self {
+            origin,
+            row,
+            columns: None,
+        }
+    }
+
+    /// The cell of this row holding value number `col`, or the row number alone when no
+    /// column holds it.
+    fn cell(&self, col: usize) -> String {
+        let col = self
+            .columns
+            .map_or(Some(col), |columns| columns.get(col).copied());
+        match col {
+            Some(col) => cell_ref(self.origin.0 + self.row, self.origin.1 + col),
+            None => sheet_row_number(self.origin.0, self.row).to_string(),
+        }
+    }
+}
+
+/// Records a source row that no output holds; blank rows are not missed.
+fn record_missing_row(report: &mut VerifyReport, source: SheetRow, expected: &[String]) {
+    if expected.iter().all(String::is_empty) {
+        return;
+    }
+    record_difference(
+        report,
+        CellDifference {
+            source_cell: Some(source.cell(0)),
+            output_file: None,
+            output_cell: None,
+            expected: expected.join("\t"),
+            actual: String::new(),
+        },
+    );
+}
+
+fn discover_outputs(source: &Path) -> Vec<PathBuf> {
+    (1..)
+        .map(|index| build_output_path(source, index))
+        .take_while(|path| path.exists())
+        .collect()
+}
+
+/// Compares one output row with the cells it should hold. `source` is the row read from the
+/// source, with the source columns of the output when it does not keep them all.
+fn compare_rows(
+    report: &mut VerifyReport,
+    source: Option<SheetRow>,
+    expected: &[String],
+    output_file: &Path,
+    output: SheetRow,
+    actual: &[String],
+) {
+    let width = expected.len().max(actual.len());
+    for col in 0..width {
+        let expected_value = expected.get(col).map(String::as_str).unwrap_or("");
+        let actual_value = actual.get(col).map(String::as_str).unwrap_or("");
+        if expected_value == actual_value {
+            continue;
+        }
+        record_difference(
+            report,
+            CellDifference {
+                source_cell: source.map(|source| source.cell(col)),
+                output_file: Some(output_file.to_path_buf()),
+                output_cell: Some(output.cell(col)),
+                expected: expected_value.to_string(),
+                actual: actual_value.to_string(),
+            },
+        );
+    }
+}
+
+fn record_difference(report: &mut VerifyReport, difference: CellDifference) {
+    report.total_differences += 1;
+    if report.differences.len() < MAX_REPORTED_DIFFERENCES {
+        report.differences.push(difference);
+    }
+}